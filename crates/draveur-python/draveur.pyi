@@ -0,0 +1,61 @@
+# This file is automatically generated by pyo3_stub_gen
+# ruff: noqa: E501, F401, F403, F405
+
+import builtins
+import os
+import pathlib
+import typing
+__all__ = [
+    "Edge",
+    "Node",
+    "analyze",
+]
+
+@typing.final
+class Edge:
+    r"""
+    Edge between two graph nodes
+    """
+    @property
+    def sink(self) -> builtins.int:
+        r"""
+        Id of the node this edge points to
+        """
+    @property
+    def kind(self) -> typing.Optional[builtins.str]:
+        r"""
+        Edge annotation, e.g. "call", "method" or "resolves_to"
+        """
+    @property
+    def attrs(self) -> dict: ...
+    def __repr__(self) -> builtins.str: ...
+
+@typing.final
+class Node:
+    r"""
+    Graph node, e.g. a function, class, call or conditional
+    """
+    @property
+    def id(self) -> builtins.int: ...
+    @property
+    def name(self) -> typing.Optional[builtins.str]: ...
+    @property
+    def node_type(self) -> typing.Optional[builtins.str]:
+        r"""
+        Tree-sitter node type, e.g. "function_definition"
+        """
+    @property
+    def edges(self) -> builtins.list[Edge]: ...
+    @property
+    def attrs(self) -> dict: ...
+    def __repr__(self) -> builtins.str: ...
+
+def analyze(path: builtins.str | os.PathLike | pathlib.Path, decorators: typing.Optional[typing.Sequence[builtins.str]] = None, function_decorators: typing.Optional[typing.Sequence[builtins.str]] = None, threads: typing.Optional[builtins.int] = None, cache: typing.Optional[builtins.str | os.PathLike | pathlib.Path] = None) -> builtins.list[builtins.list[Node]]:
+    r"""
+    Crawls `path` and returns one list of nodes per matched function or class, root first.
+    
+    `decorators` and `function_decorators` restrict classes and functions to the given
+    decorators, by default every decorated class and every module-level function is kept.
+    With `cache`, unchanged files reuse the graphs stored in that directory by earlier calls.
+    """
+
@@ -0,0 +1,6 @@
+// writes the `.pyi` stubs of the python module next to pyproject.toml
+fn main() -> pyo3_stub_gen::Result<()> {
+    let stub = draveur_python::bindings::stub_info()?;
+    stub.generate()?;
+    Ok(())
+}
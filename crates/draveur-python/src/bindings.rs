@@ -0,0 +1,177 @@
+//! Python module exposing [`Draveur`](draveur::draveur::Draveur) to python tooling.
+//!
+//! Built with `maturin develop --features extension-module`, stubs are generated with
+//! `cargo run --bin stub_gen --features bindings` and checked against the bindings by
+//! `cargo test --features bindings`.
+
+use draveur::{NodeId, Value, resolve::resolve_calls};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3_stub_gen::define_stub_info_gatherer;
+use pyo3_stub_gen::derive::{gen_stub_pyclass, gen_stub_pyfunction, gen_stub_pymethods};
+use std::path::PathBuf;
+
+use crate::{Python as Lang, report};
+
+fn to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Boolean { bool } => bool.into_pyobject(py)?.to_owned().into_any(),
+        Value::Integer { int } => int.into_pyobject(py)?.into_any(),
+        Value::String { string } => string.into_pyobject(py)?.into_any(),
+        Value::List { list } => {
+            let items = list
+                .iter()
+                .map(|v| to_py(py, v))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items)?.into_any()
+        }
+    })
+}
+
+fn py_repr(s: Option<&str>) -> String {
+    match s {
+        Some(s) => format!("{s:?}"),
+        None => "None".to_string(),
+    }
+}
+
+fn attrs_to_py<'py>(py: Python<'py>, attrs: &draveur::Attributes) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    for (k, v) in attrs {
+        dict.set_item(k, to_py(py, v)?)?;
+    }
+    Ok(dict)
+}
+
+/// Edge between two graph nodes
+#[gen_stub_pyclass]
+#[pyclass(module = "draveur", name = "Edge", frozen, skip_from_py_object)]
+#[derive(Clone)]
+pub struct PyEdge(draveur::Edge);
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl PyEdge {
+    /// Id of the node this edge points to
+    #[getter]
+    fn sink(&self) -> NodeId {
+        self.0.sink()
+    }
+
+    /// Edge annotation, e.g. "call", "method" or "resolves_to"
+    #[getter]
+    fn kind(&self) -> Option<&str> {
+        self.0.kind()
+    }
+
+    #[getter]
+    fn attrs<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        attrs_to_py(py, self.0.attrs())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Edge(sink={}, kind={})",
+            self.0.sink(),
+            py_repr(self.0.kind())
+        )
+    }
+}
+
+/// Graph node, e.g. a function, class, call or conditional
+#[gen_stub_pyclass]
+#[pyclass(module = "draveur", name = "Node", frozen, skip_from_py_object)]
+pub struct PyNode(draveur::Node);
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl PyNode {
+    #[getter]
+    fn id(&self) -> NodeId {
+        self.0.id()
+    }
+
+    #[getter]
+    fn name(&self) -> Option<&str> {
+        self.0.name()
+    }
+
+    /// Tree-sitter node type, e.g. "function_definition"
+    #[getter]
+    fn node_type(&self) -> Option<&str> {
+        self.0.node_type()
+    }
+
+    #[getter]
+    fn edges(&self) -> Vec<PyEdge> {
+        self.0.edges().iter().cloned().map(PyEdge).collect()
+    }
+
+    #[getter]
+    fn attrs<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        attrs_to_py(py, self.0.attrs())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Node(id={}, type={}, name={})",
+            self.0.id(),
+            py_repr(self.0.node_type()),
+            py_repr(self.0.name())
+        )
+    }
+}
+
+/// Crawls `path` and returns one list of nodes per matched function or class, root first.
+///
+/// `decorators` and `function_decorators` restrict classes and functions to the given
+/// decorators, by default every decorated class and every module-level function is kept.
+/// With `cache`, unchanged files reuse the graphs stored in that directory by earlier calls.
+#[gen_stub_pyfunction(module = "draveur")]
+#[pyfunction]
+#[pyo3(signature = (path, decorators=None, function_decorators=None, threads=None, cache=None))]
+fn analyze(
+    py: Python<'_>,
+    path: PathBuf,
+    decorators: Option<Vec<String>>,
+    function_decorators: Option<Vec<String>>,
+    threads: Option<usize>,
+    cache: Option<PathBuf>,
+) -> PyResult<Vec<Vec<PyNode>>> {
+    let graphs = py.detach(|| {
+        let mut draveur = Lang::draveur(
+            &decorators.unwrap_or_default(),
+            &function_decorators.unwrap_or_default(),
+        )?;
+        if let Some(threads) = threads {
+            draveur.threads(threads);
+        }
+        if let Some(dir) = cache {
+            draveur.cache(dir)?;
+        }
+
+        // the path as given, even when it isn't valid UTF-8
+        let mut graphs = draveur.analyze_paths(&[&path])?.graphs;
+        resolve_calls(&mut graphs);
+        Ok::<_, draveur::Error>(graphs)
+    });
+
+    let graphs = graphs.map_err(|e| PyRuntimeError::new_err(report(&e)))?;
+
+    Ok(graphs
+        .into_iter()
+        .map(|g| g.iter().cloned().map(PyNode).collect())
+        .collect())
+}
+
+#[pymodule(name = "draveur")]
+pub fn draveur_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyNode>()?;
+    m.add_class::<PyEdge>()?;
+    m.add_function(wrap_pyfunction!(analyze, m)?)?;
+    Ok(())
+}
+
+define_stub_info_gatherer!(stub_info);
@@ -1,6 +1,9 @@
-use draveur::Lang;
+use draveur::{Error, Lang, Result, config::Config, draveur::Draveur};
+
+pub use draveur::report;
 
 pub mod macros;
+pub mod server;
 
 #[cfg(feature = "bindings")]
 pub mod bindings;
@@ -15,4 +18,82 @@ impl Lang for Python {
     fn language() -> tree_sitter::Language {
         tree_sitter_python::LANGUAGE.into()
     }
+
+    fn fragment(name: &str) -> Option<String> {
+        match name {
+            "common_attributes" => Some(common_attributes!().to_string()),
+            "decorator" => Some(decorator!().to_string()),
+            "body_calls" => Some(_body_calls!()),
+            "conditionals" => Some(conditionals!()),
+            _ => None,
+        }
+    }
+}
+
+impl Python {
+    /// Rule sets a [`Config`] can enable, in the order they run
+    pub const RULE_SETS: [&'static str; 2] = ["functions", "classes"];
+
+    /// Builds a [`Draveur`] with the class and function rule sets.
+    ///
+    /// Empty allowlists match every decorated class and every module-level function
+    /// respectively, a function allowlist keeps the module-level functions it names.
+    pub fn draveur(classes: &[String], functions: &[String]) -> Result<Draveur<Python>> {
+        Self::from_config(&Config {
+            class_decorators: classes.to_vec(),
+            function_decorators: functions.to_vec(),
+            ..Default::default()
+        })
+    }
+
+    /// Builds a [`Draveur`] running the rule sets enabled in `config`, every one by default,
+    /// then its rule files, with its decorator allowlists and crawl settings
+    pub fn from_config(config: &Config) -> Result<Draveur<Python>> {
+        let rules = Self::rule_sets(config);
+        if let Some(unknown) = rules.iter().find(|r| !Self::RULE_SETS.contains(r)) {
+            return Err(Error::other(format!(
+                "unknown rule set {unknown}, expected one of: {}",
+                Self::RULE_SETS.join(", ")
+            )));
+        }
+
+        let mut draveur = Draveur::new();
+        if rules.contains(&"functions") {
+            let query = match config.function_decorators.as_slice() {
+                [] => query_functions!().to_string(),
+                // module-level like the unfiltered query, not methods nor nested functions
+                allowlist => format!("(module {})", query_decorated_functions!([allowlist])),
+            };
+            draveur.add_named("functions", query, functions_stanzas!())?;
+        }
+        if rules.contains(&"classes") {
+            let query = match config.class_decorators.as_slice() {
+                [] => query_decorated_classes!(),
+                allowlist => query_decorated_classes!([allowlist]),
+            };
+            draveur.add_named("classes", query, class_stanzas!())?;
+        }
+        for rule in &config.rule_files {
+            draveur.load(&rule.query, &rule.stanzas)?;
+        }
+        draveur.configure(config)?;
+        Ok(draveur)
+    }
+
+    /// Identifiers of the rules `config` enables: rule set names, then rule file queries
+    pub fn rule_ids(config: &Config) -> Vec<String> {
+        let sets = Self::rule_sets(config).into_iter().map(String::from);
+        let files = config
+            .rule_files
+            .iter()
+            .map(|rule| rule.query.display().to_string());
+        sets.chain(files).collect()
+    }
+
+    fn rule_sets(config: &Config) -> Vec<&str> {
+        match &config.rules {
+            Some(rules) => rules.iter().map(String::as_str).collect(),
+            None => Self::RULE_SETS.to_vec(),
+        }
+    }
 }
@@ -274,8 +274,6 @@ mod classes {
             format!(
                 r#"
                     global global_filename
-                    global global_row
-                    global global_column
                     {}{}{}{}{}{}{}{}{}{}{}
                 "#,
                 $crate::common_attributes!(),
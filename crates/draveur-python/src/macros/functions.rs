@@ -98,8 +98,6 @@ mod functions {
             format!(
                 r#"
                     global global_filename
-                    global global_row
-                    global global_column
                     {}{}{}{}{}{}{}
                 "#,
                 $crate::common_attributes!(),
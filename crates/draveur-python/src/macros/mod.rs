@@ -1,3 +1,6 @@
+// macro groups are wrapped in a module named after their file
+#![allow(clippy::module_inception)]
+
 mod calls;
 mod classes;
 mod control;
@@ -13,12 +16,10 @@ mod common {
 attribute common_attrs = node =>
     src = (source-text node),
     type = (node-type node),
-
-    ;; NOTE: offsets start at first item in the capture -> unintuitive
-    start_col = (plus global_column (start-column node)),
-    start_row = (plus global_row (start-row node)),
-    end_col = (plus global_column (end-column node)),
-    end_row = (plus global_row (end-row node))
+    start_col = (start-column node),
+    start_row = (start-row node),
+    end_col = (end-column node),
+    end_row = (end-row node)
             "#
         };
     }
@@ -18,8 +18,13 @@ mod queries {
         };
 
         // query_decorated!(class_definition, "foo", "bar")
-        ($def_type:ident, $($d:literal),+ $(,)?) => {{
-            let allowlist = [$($d),+].join(" ");
+        ($def_type:ident, $($d:literal),+ $(,)?) => {
+            $crate::_query_decorated!($def_type, [[$($d),+]])
+        };
+
+        // query_decorated!(class_definition, [allowlist]) - runtime allowlist
+        ($def_type:ident, [$list:expr]) => {{
+            let allowlist = $crate::_any_of!($list);
             format!(
                 r#"
 (decorated_definition
@@ -35,15 +40,30 @@ mod queries {
         }};
     }
 
+    /// Arguments of an `#any-of?` predicate, each name quoted and escaped so it can only
+    /// ever match as a whole, whatever it contains
+    #[macro_export]
+    macro_rules! _any_of {
+        ($list:expr) => {
+            $list
+                .iter()
+                .map(|name| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\"")))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+    }
+
     #[macro_export]
     macro_rules! query_decorated_classes {
         () => { $crate::_query_decorated!(class_definition) };
+        ([$list:expr]) => { $crate::_query_decorated!(class_definition, [$list]) };
         ($($d:literal),+ $(,)?) => { $crate::_query_decorated!(class_definition, $($d),+) };
     }
 
     #[macro_export]
     macro_rules! query_decorated_functions {
         () => { $crate::_query_decorated!(function_definition) };
+        ([$list:expr]) => { $crate::_query_decorated!(function_definition, [$list]) };
         ($($d:literal),+ $(,)?) => { $crate::_query_decorated!(function_definition, $($d),+) };
     }
 
@@ -57,7 +77,7 @@ mod queries {
 
         // dec!("foo", "bar")
         ($($d:literal),+ $(,)?) => {{
-            let allowlist = [$($d),+].join(" ");
+            let allowlist = $crate::_any_of!([$($d),+]);
             format!(
                 "(decorated_definition ({}) (#any-of? @decorator_name {})) @body",
                 $crate::decorator!(),
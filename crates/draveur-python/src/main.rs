@@ -1,34 +1,455 @@
-use draveur_python::{
-    Python, class_stanzas, functions_stanzas, query_decorated_classes, query_functions,
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use draveur::{
+    Diagnostic, Error, Graph, IoErrorKind, Node, Result,
+    config::Config,
+    crawl,
+    document::{Document, Report},
+    draveur::{Analysis, Draveur},
+    render::{Ascii, Dot, Mermaid, Render, Svg},
+    resolve::resolve_calls,
 };
+use draveur_python::{Python, report, server};
 
-use draveur::{Result, draveur::Draveur};
-use std::time::Instant;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-fn main() -> Result<()> {
-    let now = Instant::now();
+/// Render workflow-like python code as graphs
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Crawl paths and write the resulting graphs
+    Analyze {
+        #[command(flatten)]
+        crawl: CrawlArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Crawl paths and render the resulting graphs
+    Render {
+        #[command(flatten)]
+        crawl: CrawlArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Crawl paths and report whether every file could be processed
+    Check {
+        #[command(flatten)]
+        crawl: CrawlArgs,
+    },
+    /// Crawl paths then write the graphs again whenever files change
+    Watch {
+        #[command(flatten)]
+        crawl: CrawlArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Serve the viewer, pushing new graphs to it whenever files change
+    Serve {
+        #[command(flatten)]
+        crawl: CrawlArgs,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        #[arg(short, long, default_value_t = 3000)]
+        port: u16,
+    },
+}
+
+#[derive(Args)]
+struct CrawlArgs {
+    /// Files or directories to crawl
+    #[arg(default_value = ".")]
+    paths: Vec<PathBuf>,
+
+    /// Only keep classes with this decorator (repeatable, defaults to the config or all decorated classes)
+    #[arg(short = 'c', long = "class-decorator", value_name = "DECORATOR")]
+    classes: Vec<String>,
+
+    /// Only keep module functions with this decorator (repeatable, defaults to the config or all module functions)
+    #[arg(short = 'f', long = "function-decorator", value_name = "DECORATOR")]
+    functions: Vec<String>,
+
+    /// Number of crawler threads, defaults to the config, $THREADS or the available parallelism
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Skip files failing to process and report them instead of stopping at the first one
+    #[arg(short, long)]
+    keep_going: bool,
+
+    /// Give up on files taking longer than this many milliseconds, reporting them as failed
+    #[arg(long, value_name = "MS")]
+    timeout: Option<u64>,
+
+    /// Reuse the graphs of unchanged files from this cache directory
+    #[arg(long, value_name = "DIR")]
+    cache: Option<PathBuf>,
+
+    /// Read settings from this file instead of the draveur.toml or pyproject.toml found at
+    /// the root of the first path, flags take precedence over either
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Output format [default: the config, else pretty for analyze, mermaid for render]
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Write to a file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Only output graphs whose root function or class has this name (repeatable)
+    #[arg(long, value_name = "NAME")]
+    root: Vec<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Compact json document with the graphs and details of the run
+    Json,
+    /// Pretty-printed json document
+    Pretty,
+    /// One compact json array per graph and line, written as files are parsed, without the
+    /// details of the run nor `resolves_to` links
+    Ndjson,
+    /// Mermaid flowchart
+    Mermaid,
+    /// Graphviz DOT, clustered by file and class
+    Dot,
+    /// Box-drawing diagram for the terminal
+    Ascii,
+    /// Standalone SVG document
+    Svg,
+}
+
+impl CrawlArgs {
+    fn config(&self) -> Result<Config> {
+        let mut config = match (&self.config, self.paths.first()) {
+            (Some(file), _) => Config::load(file)?,
+            (None, Some(root)) => Config::discover(root)?.unwrap_or_default(),
+            (None, None) => Config::default(),
+        };
+
+        if !self.classes.is_empty() {
+            config.class_decorators = self.classes.clone();
+        }
+        if !self.functions.is_empty() {
+            config.function_decorators = self.functions.clone();
+        }
+        if let Some(threads) = self.threads {
+            config.threads = Some(threads);
+        }
+        Ok(config)
+    }
+
+    fn draveur(&self, config: &Config) -> Result<Draveur<Python>> {
+        let mut draveur = Python::from_config(config)?;
+        draveur.fail_fast(!self.keep_going);
+        if let Some(timeout) = self.timeout {
+            draveur.timeout(Duration::from_millis(timeout));
+        }
+        if let Some(dir) = &self.cache {
+            draveur.cache(dir)?;
+        }
+        Ok(draveur)
+    }
+
+    fn analyze(&self, config: &Config) -> Result<Analysis> {
+        let draveur = self.draveur(config)?;
+
+        let mut analysis = draveur.analyze_paths(&self.paths)?;
+
+        // link calls across every crawled path
+        resolve_calls(&mut analysis.graphs);
+        warn(&analysis.diagnostics);
+        Ok(analysis)
+    }
+
+    /// Document of `graphs` along with the details of this run
+    fn document<'a, 'd>(
+        &self,
+        config: &Config,
+        graphs: &'a [Graph],
+        files: usize,
+        diagnostics: impl IntoIterator<Item = &'d Diagnostic>,
+        elapsed: Duration,
+    ) -> Document<'a> {
+        Document {
+            roots: self.paths.iter().map(|p| p.display().to_string()).collect(),
+            rules: Python::rule_ids(config),
+            elapsed_ms: elapsed.as_millis() as u64,
+            files,
+            diagnostics: diagnostics.into_iter().map(Report::from).collect(),
+            ..Document::new(graphs)
+        }
+    }
+}
+
+fn warn<'d>(diagnostics: impl IntoIterator<Item = &'d Diagnostic>) {
+    for diagnostic in diagnostics {
+        eprintln!("warning: {}", report(diagnostic));
+    }
+}
+
+impl OutputArgs {
+    fn name(&self) -> PathBuf {
+        self.output.clone().unwrap_or_else(|| "<stdout>".into())
+    }
+
+    fn writer(&self) -> Result<Box<dyn Write>> {
+        match &self.output {
+            Some(path) => {
+                let file = File::create(path).map_err(|e| IoErrorKind::create(path, e))?;
+                Ok(Box::new(BufWriter::new(file)))
+            }
+            None => Ok(Box::new(BufWriter::new(io::stdout().lock()))),
+        }
+    }
+
+    /// Format from the flag, else from the config, else `default`
+    fn format(&self, config: &Config, default: Format) -> Result<Format> {
+        match (self.format, &config.format) {
+            (Some(format), _) => Ok(format),
+            (None, Some(name)) => Format::from_str(name, true)
+                .map_err(|e| Error::other(format!("invalid format in config: {e}"))),
+            (None, None) => Ok(default),
+        }
+    }
+
+    /// Whether `graph` is output, i.e. its root has one of the names asked for if any
+    fn selected(&self, graph: &Graph) -> bool {
+        let name = graph.root().and_then(|r| r.name());
+        self.root.is_empty() || self.root.iter().any(|r| Some(r.as_str()) == name)
+    }
+
+    fn write_line(&self, w: &mut impl Write, graph: &Graph) -> Result<()> {
+        serde_json::to_writer(&mut *w, graph)?;
+        writeln!(w).map_err(|e| IoErrorKind::write(self.name(), e))?;
+        Ok(())
+    }
+
+    /// Writes the graphs of each file crawled by `crawl` as soon as they are parsed.
+    ///
+    /// Calls can only be linked once every graph is known, so there are no `resolves_to`
+    /// links between graphs.
+    fn stream(&self, crawl: &CrawlArgs, config: &Config) -> Result<()> {
+        let draveur = crawl.draveur(config)?;
+        let mut w = self.writer()?;
+
+        let mut failed = None;
+        let diagnostics = draveur.stream_paths(&crawl.paths, |_, graphs| {
+            for graph in graphs.iter().filter(|g| self.selected(g)) {
+                if failed.is_none()
+                    && let Err(e) = self.write_line(&mut w, graph)
+                {
+                    // no use parsing what can't be written
+                    draveur.cancellation_token().cancel();
+                    failed = Some(e);
+                }
+            }
+        });
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        warn(&diagnostics?);
+        w.flush().map_err(|e| IoErrorKind::write(self.name(), e))?;
+        Ok(())
+    }
+
+    fn write(&self, mut document: Document, format: Format) -> Result<()> {
+        let mut w = self.writer()?;
+
+        if !self.root.is_empty() {
+            let mut selected = document
+                .graphs
+                .iter()
+                .filter(|g| self.selected(g))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            // calls linked to graphs left out would point nowhere
+            let ids = selected
+                .iter()
+                .flat_map(Graph::iter)
+                .map(Node::id)
+                .collect::<HashSet<_>>();
+            for node in selected.iter_mut().flat_map(Graph::iter_mut) {
+                node.retain_edges(|edge| ids.contains(&edge.sink()));
+            }
+            document.graphs = Cow::Owned(selected);
+        }
+        let graphs = &document.graphs;
+
+        match format {
+            Format::Json | Format::Pretty => {
+                match format {
+                    Format::Json => document.write(&mut w)?,
+                    _ => document.write_pretty(&mut w)?,
+                }
+                writeln!(w).map_err(|e| IoErrorKind::write(self.name(), e))?;
+            }
+            Format::Ndjson => {
+                for graph in graphs.iter() {
+                    self.write_line(&mut w, graph)?;
+                }
+            }
+            Format::Mermaid | Format::Dot | Format::Ascii | Format::Svg => {
+                let diagram = match format {
+                    Format::Mermaid => Mermaid::default().to_string(graphs),
+                    Format::Dot => Dot::default().to_string(graphs),
+                    Format::Svg => Svg::default().to_string(graphs),
+                    _ => Ascii {
+                        max_width: terminal_width(),
+                        ..Default::default()
+                    }
+                    .to_string(graphs),
+                };
+                w.write_all(diagram.as_bytes())
+                    .map_err(|e| IoErrorKind::write(self.name(), e))?;
+            }
+        }
+        w.flush().map_err(|e| IoErrorKind::write(self.name(), e))?;
+        Ok(())
+    }
+}
+
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(100)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", report(&e));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let mut command = cli.command;
+    let (Command::Analyze { crawl, .. }
+    | Command::Render { crawl, .. }
+    | Command::Check { crawl }
+    | Command::Watch { crawl, .. }
+    | Command::Serve { crawl, .. }) = &mut command;
+    // overlapping paths would crawl their files twice
+    crawl.paths = crawl::roots(&crawl.paths);
 
-    let classes = query_decorated_classes!(
-        "workflows.workflow.define",
-        "workflows.update",
-        "workflows.query",
-        "workflows.signal",
-        "workflows.activity",
-        "foo"
-    );
-    let functions = query_functions!().to_string();
+    match command {
+        Command::Analyze { crawl, output } => {
+            let config = crawl.config()?;
+            let format = output.format(&config, Format::Pretty)?;
+            if let Format::Ndjson = format {
+                return output.stream(&crawl, &config);
+            }
+            let now = Instant::now();
+            let analysis = crawl.analyze(&config)?;
+            output.write(
+                crawl.document(
+                    &config,
+                    &analysis.graphs,
+                    analysis.files,
+                    &analysis.diagnostics,
+                    now.elapsed(),
+                ),
+                format,
+            )?;
+        }
+        Command::Render { crawl, output } => {
+            let config = crawl.config()?;
+            let format = output.format(&config, Format::Mermaid)?;
+            if let Format::Ndjson = format {
+                return output.stream(&crawl, &config);
+            }
+            let graphs = crawl.analyze(&config)?.graphs;
+            output.write(Document::new(&graphs), format)?;
+        }
+        Command::Check { crawl } => {
+            let now = Instant::now();
+            let draveur = crawl.draveur(&crawl.config()?)?;
 
-    let graphs = Draveur::<Python>::new()
-        .add(functions, functions_stanzas!())?
-        .add(classes, class_stanzas!())?
-        .waltz("/Users/naten/coding/rust/draveur/")?;
+            // nothing to link, so count graphs as they come instead of holding them
+            let mut graphs = 0;
+            let diagnostics = draveur.stream_paths(&crawl.paths, |_, g| graphs += g.len())?;
+            warn(&diagnostics);
+            if !diagnostics.is_empty() {
+                return Err(Error::other(format!(
+                    "{} file(s) failed",
+                    diagnostics.len()
+                )));
+            }
+            eprintln!(
+                "ok: {graphs} graphs from {} path(s) in {:?}",
+                crawl.paths.len(),
+                now.elapsed()
+            );
+        }
+        Command::Watch { crawl, output } => {
+            let config = crawl.config()?;
+            let format = output.format(&config, Format::Json)?;
+            let draveur = crawl.draveur(&config)?;
+            let mut watch = draveur.watch(&crawl.paths)?;
 
-    let elapsed = now.elapsed();
+            while let Some(delta) = watch.next() {
+                match delta {
+                    Ok(delta) => {
+                        let mut graphs = watch.graphs().cloned().collect::<Vec<_>>();
+                        resolve_calls(&mut graphs);
+                        // files failing since earlier deltas are still reported
+                        let failed = watch.diagnostics();
+                        output.write(
+                            crawl.document(
+                                &config,
+                                &graphs,
+                                watch.file_count(),
+                                failed.values(),
+                                delta.elapsed,
+                            ),
+                            format,
+                        )?;
 
-    for graph in graphs {
-        println!("{}", serde_json::to_string_pretty(&graph)?);
+                        warn(delta.failed.iter().filter_map(|path| failed.get(path)));
+                        eprintln!(
+                            "updated {} file(s), removed {} in {:?}",
+                            delta.updated.len(),
+                            delta.removed.len(),
+                            delta.elapsed
+                        );
+                    }
+                    Err(e) => eprintln!("error: {}", report(&e)),
+                }
+            }
+        }
+        Command::Serve { crawl, host, port } => {
+            let draveur = crawl.draveur(&crawl.config()?)?;
+            let addr = format!("{host}:{port}");
+            let listener = TcpListener::bind(&addr)
+                .map_err(|e| Error::other(format!("failed to bind {addr}: {e}")))?;
+            server::serve(&draveur, &crawl.paths, listener)?;
+        }
     }
 
-    // println!("{:?}", elapsed);
     Ok(())
 }
@@ -0,0 +1,330 @@
+//! Serves the viewer and pushes graphs to it over a WebSocket whenever files change.
+//!
+//! Messages keep the shape `viewer/server.js` uses: `{type: "graph", data, elapsed}` with the
+//! nodes of every graph in `data`, `{type: "error", message}`, and clients can send
+//! `{type: "reload"}` to get everything analyzed again.
+//!
+//! The server runs until the cancellation token of its [`Draveur`] is cancelled.
+
+use crate::{Python, report};
+use draveur::{
+    Diagnostic, Error, Graph, IoErrorKind, Node, Result, draveur::Draveur, resolve::resolve_calls,
+};
+use serde_json::json;
+use tungstenite::error::ProtocolError;
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const INDEX: &str = include_str!("../../../viewer/index.html");
+
+// how long a client waits for messages before checking for broadcasts, and the accept loop
+// for connections, or after failing to accept one, before checking for shutdown
+const POLL: Duration = Duration::from_millis(100);
+
+// longest request head read, and how long a client may take to send it
+const MAX_HEAD: u64 = 16 * 1024;
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct Hub {
+    // last graph message, sent to clients as they connect
+    latest: Mutex<Option<String>>,
+    clients: Mutex<Vec<Sender<String>>>,
+}
+
+impl Hub {
+    fn graph(&self, mut graphs: Vec<Graph>, elapsed: Duration) {
+        resolve_calls(&mut graphs);
+        let nodes = graphs.iter().flat_map(|g| g.iter()).collect::<Vec<&Node>>();
+        let msg = json!({
+            "type": "graph",
+            "data": nodes,
+            "elapsed": elapsed.as_millis() as u64,
+        })
+        .to_string();
+
+        let mut latest = self.latest.lock().unwrap();
+        *latest = Some(msg.clone());
+        self.broadcast(msg);
+    }
+
+    fn error(&self, e: &Error) {
+        eprintln!("error: {}", report(e));
+        self.broadcast(json!({"type": "error", "message": report(e)}).to_string());
+    }
+
+    /// Reported after the graph so the viewer shows that some files are missing
+    fn diagnostics<'d>(&self, diagnostics: impl IntoIterator<Item = &'d Diagnostic>) {
+        let message = diagnostics
+            .into_iter()
+            .map(|d| report(d))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if message.is_empty() {
+            return;
+        }
+        eprintln!("{message}");
+        self.broadcast(json!({"type": "error", "message": message}).to_string());
+    }
+
+    fn broadcast(&self, msg: String) {
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|client| client.send(msg.clone()).is_ok());
+    }
+
+    fn subscribe(&self) -> Receiver<String> {
+        let (tx, rx) = channel();
+
+        // hold `latest` so no graph gets broadcast between the two
+        let latest = self.latest.lock().unwrap();
+        if let Some(msg) = &*latest {
+            let _ = tx.send(msg.clone());
+        }
+        self.clients.lock().unwrap().push(tx);
+        rx
+    }
+}
+
+struct Server<'a> {
+    draveur: &'a Draveur<Python>,
+    paths: Vec<PathBuf>,
+    hub: Hub,
+}
+
+impl Server<'_> {
+    fn reload(&self) {
+        let start = Instant::now();
+        match self.draveur.analyze_paths(&self.paths) {
+            Ok(analysis) => {
+                self.hub.graph(analysis.graphs, start.elapsed());
+                self.hub.diagnostics(&analysis.diagnostics);
+            }
+            Err(e) => self.hub.error(&e),
+        }
+    }
+
+    fn shutting_down(&self) -> bool {
+        self.draveur.cancellation_token().is_cancelled()
+    }
+
+    fn handle(&self, stream: TcpStream) -> Result<()> {
+        let head = Head::read(&stream).map_err(|e| IoErrorKind::read("<socket>", e))?;
+        let result = match head.header("upgrade") {
+            Some(upgrade) if upgrade.eq_ignore_ascii_case("websocket") => {
+                return self.client(stream, &head);
+            }
+            _ => page(&stream, &head.path),
+        };
+        result.map_err(|e| IoErrorKind::write("<socket>", e).into())
+    }
+
+    fn client(&self, stream: TcpStream, head: &Head) -> Result<()> {
+        let Some(key) = head.header("sec-websocket-key") else {
+            let result = respond(&stream, "400 Bad Request", "text/plain", "missing key");
+            return result.map_err(|e| IoErrorKind::write("<socket>", e).into());
+        };
+        write!(
+            &stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            derive_accept_key(key.as_bytes())
+        )
+        .map_err(|e| IoErrorKind::write("<socket>", e))?;
+
+        stream
+            .set_read_timeout(Some(POLL))
+            .map_err(|e| IoErrorKind::read("<socket>", e))?;
+        let mut ws = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+        let rx = self.hub.subscribe();
+        loop {
+            if self.shutting_down() {
+                let _ = ws.close(None);
+                let _ = ws.flush();
+                return Ok(());
+            }
+
+            for msg in rx.try_iter() {
+                ws.send(Message::text(msg))
+                    .map_err(|e| Error::other(e.to_string()))?;
+            }
+
+            match ws.read() {
+                Ok(Message::Text(text)) => {
+                    let msg = serde_json::from_str::<serde_json::Value>(&text).ok();
+                    match msg.as_ref().and_then(|m| m.get("type")?.as_str()) {
+                        Some("reload") => self.reload(),
+                        _ => eprintln!("invalid message: {text}"),
+                    }
+                }
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(
+                    tungstenite::Error::ConnectionClosed
+                    | tungstenite::Error::AlreadyClosed
+                    | tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake),
+                ) => return Ok(()),
+                Err(e) => return Err(Error::other(e.to_string())),
+            }
+        }
+    }
+}
+
+/// Request line and headers of an HTTP request
+struct Head {
+    path: String,
+    // by lowercase name
+    headers: HashMap<String, String>,
+}
+
+impl Head {
+    /// Reads up to the blank line ending the headers, however many packets they span.
+    ///
+    /// Bytes following the head may be buffered and lost, which is fine as WebSocket clients
+    /// wait for the handshake response before sending anything else.
+    fn read(stream: &TcpStream) -> io::Result<Self> {
+        stream.set_read_timeout(Some(HEAD_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.take(MAX_HEAD));
+
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "request head ended early",
+                ));
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+        Ok(Self { path, headers })
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// Answers a plain HTTP request for `path` with the viewer page
+fn page(stream: &TcpStream, path: &str) -> io::Result<()> {
+    match path {
+        "/" | "/index.html" => respond(stream, "200 OK", "text/html; charset=utf-8", INDEX),
+        _ => respond(stream, "404 Not Found", "text/plain", "not found"),
+    }
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Serves the viewer on `listener` and keeps it up to date with the graphs of `paths`, until
+/// the cancellation token of `draveur` is cancelled
+pub fn serve(draveur: &Draveur<Python>, paths: &[PathBuf], listener: TcpListener) -> Result<()> {
+    // same absolute filenames for reloads and watched updates
+    let paths = paths
+        .iter()
+        .map(|p| p.canonicalize().map_err(|e| IoErrorKind::open(p, e)))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let addr = listener
+        .local_addr()
+        .map_err(|e| IoErrorKind::read("<socket>", e))?;
+    // accepting without blocking lets the accept loop notice shutdowns
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| IoErrorKind::read("<socket>", e))?;
+
+    let mut watch = draveur.watch(&paths)?;
+
+    let server = Server {
+        draveur,
+        paths: paths.clone(),
+        hub: Hub::default(),
+    };
+
+    eprintln!("Server running at http://{addr}");
+    for path in &paths {
+        eprintln!("Watching: {}", path.display());
+    }
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            while !server.shutting_down() {
+                let stream = match listener.accept() {
+                    Ok((stream, _)) => stream,
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        thread::sleep(POLL);
+                        continue;
+                    }
+                    // e.g. out of file descriptors, which retrying right away won't fix
+                    Err(e) => {
+                        let error = Error::from(IoErrorKind::read("<socket>", e));
+                        eprintln!("error: {}", report(&error));
+                        thread::sleep(POLL);
+                        continue;
+                    }
+                };
+                let server = &server;
+                s.spawn(move || {
+                    let result = stream
+                        .set_nonblocking(false)
+                        .map_err(|e| IoErrorKind::read("<socket>", e).into())
+                        .and_then(|_| server.handle(stream));
+                    if let Err(e) = result {
+                        eprintln!("error: {}", report(&e));
+                    }
+                });
+            }
+        });
+
+        // ends once cancelled
+
+        while let Some(delta) = watch.next() {
+            match delta {
+                Ok(delta) => {
+                    eprintln!(
+                        "updated {} file(s), removed {} in {:?}",
+                        delta.updated.len(),
+                        delta.removed.len(),
+                        delta.elapsed
+                    );
+                    server
+                        .hub
+                        .graph(watch.graphs().cloned().collect(), delta.elapsed);
+                    // every file still failing, not only the ones of this delta
+                    server.hub.diagnostics(watch.diagnostics().values());
+                }
+                Err(e) => server.hub.error(&e),
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+#![cfg(feature = "bindings")]
+
+mod common;
+
+use common::project;
+use draveur_python::bindings::{draveur_module, stub_info};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::fs;
+
+#[test]
+fn analyze_returns_linked_graphs() {
+    let dir = project(
+        "bindings",
+        &[
+            ("app.py", "def main():\n    load()\n"),
+            ("storage.py", "def load():\n    pass\n"),
+        ],
+    );
+
+    Python::initialize();
+    Python::attach(|py| {
+        let module = PyModule::new(py, "draveur").unwrap();
+        draveur_module(&module).unwrap();
+        let analyze = module.getattr("analyze").unwrap();
+
+        // paths are taken as strings or os.PathLike
+        let path = py
+            .import("pathlib")
+            .unwrap()
+            .getattr("Path")
+            .unwrap()
+            .call1((&dir,))
+            .unwrap();
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("threads", 1).unwrap();
+        let graphs = analyze.call((path,), Some(&kwargs)).unwrap();
+
+        let names = graphs
+            .try_iter()
+            .unwrap()
+            .map(|graph| {
+                let root = graph.unwrap().get_item(0).unwrap();
+                root.getattr("name").unwrap().extract::<String>().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["main", "load"]);
+
+        // calls are resolved across files
+        let kinds = graphs
+            .get_item(0)
+            .unwrap()
+            .get_item(1)
+            .unwrap()
+            .getattr("edges")
+            .unwrap()
+            .try_iter()
+            .unwrap()
+            .map(|edge| {
+                edge.unwrap()
+                    .getattr("kind")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(kinds.iter().any(|k| k == "resolves_to"), "{kinds:?}");
+
+        let error = analyze.call1((dir.join("missing"),)).unwrap_err();
+        assert!(error.to_string().starts_with("RuntimeError"), "{error}");
+    });
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn analyze_takes_paths_that_are_not_utf8() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = project("bindings-bytes", &[]);
+    // "café" in latin-1
+    let nested = dir.join(OsStr::from_bytes(b"caf\xe9"));
+    fs::create_dir(&nested).unwrap();
+    fs::write(nested.join("app.py"), "def main():\n    load()\n").unwrap();
+
+    Python::initialize();
+    Python::attach(|py| {
+        let module = PyModule::new(py, "draveur").unwrap();
+        draveur_module(&module).unwrap();
+        let graphs = module
+            .getattr("analyze")
+            .unwrap()
+            .call1((&nested,))
+            .unwrap();
+        assert_eq!(graphs.len().unwrap(), 1);
+    });
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stubs_are_up_to_date() {
+    let stub = stub_info().unwrap();
+    let generated = stub.modules["draveur"].format_with_config(stub.config.use_type_statement);
+    assert_eq!(
+        generated,
+        include_str!("../draveur.pyi"),
+        "stubs are out of date, run `cargo run --bin stub_gen --features bindings`"
+    );
+}
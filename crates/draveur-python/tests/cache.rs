@@ -0,0 +1,126 @@
+mod common;
+
+use common::project;
+use draveur::cache::Cache;
+use draveur::config::Config;
+use draveur::hash::hash;
+use draveur::testing::RuleTest;
+use draveur::{Graph, Result};
+use draveur_python::{Python, report};
+use std::fs;
+use std::path::Path;
+
+const SOURCE: &str = r#"
+@task
+def load(path):
+    data = read(path)
+
+def plain():
+    go()
+
+def other():
+    stop()
+"#;
+
+fn graphs() -> Vec<Graph> {
+    let draveur = Python::draveur(&[], &[]).unwrap();
+    RuleTest::with(draveur).path("app.py").run(SOURCE).unwrap()
+}
+
+#[test]
+fn entries_are_read_back() {
+    let dir = project("entries", &[]);
+    let cache = Cache::new(&dir).unwrap();
+    let (path, content, rules) = (Path::new("app.py"), hash(SOURCE.as_bytes()), 7);
+
+    assert_eq!(cache.get(path, content, rules), None);
+    cache.put(path, content, rules, &graphs()).unwrap();
+    assert_eq!(cache.get(path, content, rules), Some(graphs()));
+
+    // nothing but the entry is left behind
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn entries_are_invalidated() {
+    let dir = project("invalidated", &[]);
+    let cache = Cache::new(&dir).unwrap();
+    let (path, content, rules) = (Path::new("app.py"), hash(SOURCE.as_bytes()), 7);
+    cache.put(path, content, rules, &graphs()).unwrap();
+
+    assert_eq!(cache.get(path, hash(b"edited"), rules), None);
+    assert_eq!(cache.get(path, content, 8), None);
+    assert_eq!(cache.get(Path::new("other.py"), content, rules), None);
+
+    // a newer entry replaces the previous one
+    cache.put(path, content, 8, &[]).unwrap();
+    assert_eq!(cache.get(path, content, rules), None);
+    assert_eq!(cache.get(path, content, 8), Some(vec![]));
+
+    // corrupted entries are misses
+    let entry = fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+    fs::write(entry.path(), "{").unwrap();
+    assert_eq!(cache.get(path, content, 8), None);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rule_changes_invalidate_entries() {
+    let dir = project("rules", &[]);
+    let source = dir.join("src");
+    fs::create_dir(&source).unwrap();
+    fs::write(source.join("app.py"), SOURCE).unwrap();
+
+    let analyze = |config: &Config, cache: bool| -> Result<Vec<Graph>> {
+        let mut draveur = Python::from_config(config)?;
+        if cache {
+            draveur.cache(dir.join("cache"))?;
+        }
+        Ok(draveur.analyze(&source.to_string_lossy())?.graphs)
+    };
+
+    let all = Config::default();
+    let tasks = Config {
+        function_decorators: vec!["task".to_string()],
+        ..Default::default()
+    };
+
+    let cached = analyze(&all, true).unwrap();
+    assert_eq!(cached, analyze(&all, false).unwrap());
+    assert_eq!(analyze(&all, true).unwrap(), cached);
+
+    // the decorated function, not the cached graphs of the undecorated ones
+    let filtered = analyze(&tasks, true).unwrap();
+    assert_eq!(filtered, analyze(&tasks, false).unwrap());
+    assert_eq!((filtered.len(), cached.len()), (1, 2));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unwritable_caches_are_skipped() {
+    let dir = project("unwritable", &[]);
+    let source = dir.join("src");
+    fs::create_dir(&source).unwrap();
+    fs::write(source.join("app.py"), SOURCE).unwrap();
+
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.cache(dir.join("cache")).unwrap();
+    // entries can't be written once the directory is gone
+    fs::remove_dir(dir.join("cache")).unwrap();
+
+    let analysis = draveur.analyze(&source.to_string_lossy()).unwrap();
+    assert_eq!(analysis.graphs.len(), graphs().len());
+    assert!(analysis.diagnostics.is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn uncreatable_caches_are_reported() {
+    let dir = project("uncreatable", &[("file", "")]);
+
+    let error = Cache::new(dir.join("file/cache")).unwrap_err();
+    let message = report(&error);
+    assert!(message.starts_with("failed to create"), "{message}");
+    fs::remove_dir_all(dir).unwrap();
+}
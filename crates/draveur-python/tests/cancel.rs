@@ -0,0 +1,141 @@
+mod common;
+
+use common::project;
+use draveur::Error;
+use draveur::cancel::CancellationToken;
+use draveur_python::Python;
+use std::fs;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+// a few thousand functions, long enough to outlast a tight budget
+fn source() -> String {
+    (0..2000)
+        .map(|i| format!("def f{i}(x):\n    y = g{i}(x)\n    h(y)\n\n"))
+        .collect()
+}
+
+#[test]
+fn files_out_of_time_fail() {
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.timeout(Duration::from_millis(1));
+
+    let error = draveur.analyze_source("big.py", source().as_bytes()).err();
+    assert!(matches!(error, Some(Error::Timeout { .. })), "{error:?}");
+}
+
+#[test]
+fn cancelled_files_fail_even_without_matches() {
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.cancellation(token.clone());
+    token.cancel();
+
+    // the query matches nothing, so only the matching step can notice
+    let error = draveur.analyze_source("empty.py", b"x = 1\n").err();
+    assert!(matches!(error, Some(Error::Cancelled)), "{error:?}");
+}
+
+// many files of `functions` functions each, more than workers get through before being
+// cancelled
+fn big_project(name: &str, functions: usize) -> std::path::PathBuf {
+    let source = source()
+        .split_inclusive("\n\n")
+        .take(functions)
+        .collect::<String>();
+    let names = (0..64).map(|i| format!("m{i:02}.py")).collect::<Vec<_>>();
+    let files = names
+        .iter()
+        .map(|name| (name.as_str(), source.as_str()))
+        .collect::<Vec<_>>();
+    project(name, &files)
+}
+
+#[test]
+fn crawls_stop_once_cancelled_from_another_thread() {
+    let dir = big_project("cancel-waltz", 2000);
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.threads(2).cancellation(token.clone());
+
+    let error = thread::scope(|s| {
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+        draveur.waltz(&dir.to_string_lossy()).err()
+    });
+    assert!(matches!(error, Some(Error::Cancelled)), "{error:?}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn streams_deliver_nothing_once_cancelled() {
+    let dir = big_project("cancel-stream", 200);
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.threads(2).cancellation(token.clone());
+
+    let (cancel, cancelling) = mpsc::channel::<()>();
+    let (cancelled, done) = mpsc::channel::<()>();
+    let (mut before, mut after) = (0, 0);
+    let canceller = token.clone();
+    let result = thread::scope(|s| {
+        s.spawn(move || {
+            cancelling.recv().unwrap();
+            canceller.cancel();
+            cancelled.send(()).unwrap();
+        });
+        draveur.stream(&dir.to_string_lossy(), |_, _| match before {
+            0 => {
+                // cancelled by the other thread while workers keep filling the queue
+                before += 1;
+                cancel.send(()).unwrap();
+                done.recv().unwrap();
+            }
+            _ if token.is_cancelled() => after += 1,
+            _ => before += 1,
+        })
+    });
+
+    assert!(matches!(result, Err(Error::Cancelled)), "{result:?}");
+    assert_eq!(after, 0);
+    assert!(before < 64, "{before}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn watches_run_again_once_reset() {
+    let dir = project("cancel-watch", &[("app.py", "def main():\n    run()\n")])
+        .canonicalize()
+        .unwrap();
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.cancellation(token.clone());
+    let mut watch = draveur.watch(&[&dir]).unwrap();
+    watch.next().unwrap().unwrap();
+
+    token.cancel();
+    assert!(watch.next().is_none());
+
+    // the same token and watch pick up changes again
+    token.reset();
+    fs::write(dir.join("app.py"), "def main():\n    load()\n").unwrap();
+    let (next, finished) = mpsc::channel::<()>();
+    let delta = thread::scope(|s| {
+        s.spawn(move || {
+            // ends the watch should the change never be seen
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(Duration::from_secs(5)) {
+                token.cancel();
+            }
+        });
+        let delta = watch.next();
+        drop(next);
+        delta
+    });
+    let delta = delta.expect("no delta after the reset").unwrap();
+    let updated = delta.updated.iter().map(|(p, _)| p).collect::<Vec<_>>();
+    assert_eq!(updated, [&dir.join("app.py")]);
+    fs::remove_dir_all(dir).unwrap();
+}
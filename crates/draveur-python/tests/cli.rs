@@ -0,0 +1,145 @@
+mod common;
+
+use common::project;
+use draveur::Graph;
+use draveur::document::Document;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn draveur(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_draveur-python"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn overlapping_paths_are_crawled_once() {
+    let dir = project(
+        "overlap",
+        &[
+            ("src/app.py", "def main():\n    load()\n"),
+            ("src/storage.py", "def load():\n    pass\n"),
+        ],
+    );
+
+    let args = ["analyze", "--format", "json", "src", "./src/", "src/app.py"];
+    let output = draveur(&args, &dir);
+    assert!(output.status.success(), "{output:?}");
+
+    let document = Document::read(output.stdout.as_slice()).unwrap();
+    assert_eq!(document.roots, ["src"]);
+    assert_eq!(document.files, 2);
+    assert_eq!(document.graphs.len(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unwritable_outputs_are_reported() {
+    let dir = project("output", &[("app.py", "def main():\n    run()\n")]);
+
+    let output = draveur(&["analyze", "-o", "missing/out.json", "."], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("error: failed to create missing/out.json"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ids_do_not_depend_on_the_run() {
+    let dir = project(
+        "ids",
+        &[
+            ("app.py", "def main():\n    load()\n"),
+            ("pkg/storage.py", "def load():\n    pass\n"),
+        ],
+    );
+    let ids = |args: &[&str], cwd: &Path| {
+        let output = draveur(&[&["analyze", "--format", "json"], args].concat(), cwd);
+        assert!(output.status.success(), "{output:?}");
+        let document = Document::read(output.stdout.as_slice()).unwrap();
+        document
+            .graphs
+            .iter()
+            .flat_map(|g| g.iter().map(|n| n.id()))
+            .collect::<Vec<_>>()
+    };
+
+    let expected = ids(&["-j", "1", "."], &dir);
+    assert_eq!(expected.len(), 3);
+    let absolute = dir.to_string_lossy();
+    for args in [&["-j", "4", "."][..], &["./"], &[&absolute]] {
+        assert_eq!(ids(args, &dir), expected, "{args:?}");
+    }
+
+    // files are named relative to the crawled path, wherever it lives
+    let storage = ids(&["."], &dir.join("pkg"));
+    assert_eq!(ids(&["pkg"], &dir), storage);
+    assert_eq!(ids(&["./pkg/storage.py"], &dir), storage);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ids_are_unique_across_paths() {
+    let source = "def main():\n    load()\n";
+    let dir = project("roots", &[("a/app.py", source), ("b/app.py", source)]);
+
+    for format in ["json", "ndjson"] {
+        let output = draveur(&["analyze", "--format", format, "a", "b"], &dir);
+        assert!(output.status.success(), "{output:?}");
+        let graphs = match format {
+            // reading the document checks that ids are unique across its graphs
+            "json" => Document::read(output.stdout.as_slice())
+                .unwrap()
+                .into_graphs(),
+            _ => String::from_utf8(output.stdout)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<Graph>(line).unwrap())
+                .collect(),
+        };
+        let ids = graphs
+            .iter()
+            .flat_map(Graph::iter)
+            .map(|n| n.id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 4, "{format}");
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 4, "{format}");
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ndjson_graphs_are_not_linked() {
+    let dir = project(
+        "ndjson",
+        &[
+            ("app.py", "def main():\n    load()\n"),
+            ("storage.py", "def load():\n    pass\n"),
+        ],
+    );
+
+    let output = draveur(&["analyze", "--format", "ndjson", "."], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let graphs = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Graph>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(graphs.len(), 2);
+    // written before every graph is known, so calls aren't resolved
+    let kinds = graphs
+        .iter()
+        .flat_map(Graph::iter)
+        .flat_map(|n| n.edges())
+        .filter_map(|e| e.kind())
+        .collect::<Vec<_>>();
+    assert!(!kinds.contains(&"resolves_to"), "{kinds:?}");
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+// fresh directory holding `files`, named after the test
+pub fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = env::temp_dir().join(format!(
+        "draveur-{}-{name}-{}",
+        env!("CARGO_CRATE_NAME"),
+        process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (path, content) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
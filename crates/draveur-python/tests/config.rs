@@ -0,0 +1,103 @@
+mod common;
+
+use common::project;
+use draveur::Error;
+use draveur::config::Config;
+use draveur_python::{Python, report};
+use std::path::Path;
+use std::{env, fs};
+
+#[test]
+fn pyproject_is_discovered() {
+    let pyproject =
+        "[project]\nname = \"app\"\n\n[tool.draveur]\nfunction-decorators = [\"task\"]\n";
+    let dir = project(
+        "pyproject",
+        &[("pyproject.toml", pyproject), ("app.py", "")],
+    );
+
+    let config = Config::discover(&dir).unwrap().unwrap();
+    assert_eq!(config.function_decorators, ["task"]);
+
+    // a single file is crawled from its directory
+    let config = Config::discover(dir.join("app.py")).unwrap().unwrap();
+    assert_eq!(config.function_decorators, ["task"]);
+
+    // draveur.toml takes precedence
+    fs::write(dir.join("draveur.toml"), "threads = 2\n").unwrap();
+    let config = Config::discover(&dir).unwrap().unwrap();
+    assert_eq!(
+        (config.threads, config.function_decorators.len()),
+        (Some(2), 0)
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pyprojects_without_settings_are_skipped() {
+    let dir = project(
+        "no-settings",
+        &[("pyproject.toml", "[tool.black]\nline-length = 100\n")],
+    );
+    assert!(Config::discover(&dir).unwrap().is_none());
+
+    // unless asked for explicitly
+    let error = Config::load(dir.join("pyproject.toml")).unwrap_err();
+    assert!(matches!(error, Error::Config { .. }), "{error:?}");
+    let message = report(&error);
+    assert!(
+        message.contains("missing [tool.draveur] table"),
+        "{message}"
+    );
+
+    fs::remove_file(dir.join("pyproject.toml")).unwrap();
+    assert!(Config::discover(&dir).unwrap().is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    let dir = project(
+        "unknown",
+        &[
+            ("draveur.toml", "class-decorator = [\"flow\"]\n"),
+            ("nested/pyproject.toml", "[tool.draveur]\nthread = 4\n"),
+            (
+                "rules/draveur.toml",
+                "[[rule-files]]\nquery = \"a.scm\"\nstanza = \"a.tsg\"\n",
+            ),
+        ],
+    );
+
+    for file in [
+        "draveur.toml",
+        "nested/pyproject.toml",
+        "rules/draveur.toml",
+    ] {
+        let error = Config::load(dir.join(file)).err();
+        assert!(
+            matches!(error, Some(Error::Config { .. })),
+            "{file}: {error:?}"
+        );
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rule_files_are_relative_to_the_config() {
+    let rules = "[[rule-files]]\nquery = \"rules/tasks.scm\"\nstanzas = \"rules/tasks.tsg\"\n";
+    let dir = project("rule-files", &[("project/draveur.toml", rules)]);
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    fs::create_dir_all(dir.join("project/rules")).unwrap();
+    for name in ["tasks.scm", "tasks.tsg", "common.tsg"] {
+        fs::copy(fixtures.join(name), dir.join("project/rules").join(name)).unwrap();
+    }
+
+    // found wherever draveur runs from
+    let config = Config::discover(dir.join("project")).unwrap().unwrap();
+    let rule = &config.rule_files[0];
+    assert_eq!(rule.query, dir.join("project/rules/tasks.scm"));
+    assert_eq!(rule.stanzas, dir.join("project/rules/tasks.tsg"));
+    assert!(Python::from_config(&config).is_ok());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,305 @@
+mod common;
+
+use common::project;
+use draveur::Error;
+use draveur::testing::normalize;
+use draveur_python::{Python, report};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use std::{env, fs, process};
+
+#[test]
+fn missing_paths_only_fail_fast() {
+    let missing = env::temp_dir().join(format!("draveur-crawl-missing-{}", process::id()));
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+
+    let error = draveur.analyze(&missing.to_string_lossy()).unwrap_err();
+    // the path is named once
+    let message = report(&error);
+    assert_eq!(
+        message.matches(&*missing.to_string_lossy()).count(),
+        1,
+        "{message}"
+    );
+
+    draveur.fail_fast(false);
+    let analysis = draveur.analyze(&missing.to_string_lossy()).unwrap();
+    assert!(analysis.graphs.is_empty());
+    let paths = analysis
+        .diagnostics
+        .iter()
+        .map(|d| d.path.as_path())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, [Path::new(&missing)]);
+}
+
+#[test]
+fn sources_that_are_not_utf8_still_parse() {
+    let dir = project("latin1", &[]);
+    // "café" in latin-1, legal under the coding line
+    let mut source = b"# -*- coding: latin-1 -*-\ndef main():\n    greet('caf".to_vec();
+    source.extend(b"\xe9')\n");
+    fs::write(dir.join("app.py"), source).unwrap();
+
+    let draveur = Python::draveur(&[], &[]).unwrap();
+    let analysis = draveur.analyze(&dir.to_string_lossy()).unwrap();
+    assert!(analysis.diagnostics.is_empty(), "{:?}", analysis.diagnostics);
+    let graphs = serde_json::to_value(normalize(&analysis.graphs)).unwrap();
+    let names = graphs[0]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["attrs"]["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["main", "greet"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn unreadable_directories_are_not_counted_as_files() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = project(
+        "unreadable",
+        &[
+            ("app.py", "def main():\n    run()\n"),
+            ("locked/hidden.py", "def hidden():\n    pass\n"),
+        ],
+    );
+    let locked = dir.join("locked");
+    let permissions = |mode| fs::set_permissions(&locked, fs::Permissions::from_mode(mode));
+    permissions(0o000).unwrap();
+    // e.g. running as root, which reads it anyway
+    if fs::read_dir(&locked).is_ok() {
+        permissions(0o755).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        return;
+    }
+
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.fail_fast(false);
+    let analysis = draveur.analyze(&dir.to_string_lossy()).unwrap();
+    permissions(0o755).unwrap();
+
+    assert_eq!(analysis.files, 1);
+    assert_eq!(analysis.graphs.len(), 1);
+    let paths = analysis
+        .diagnostics
+        .iter()
+        .map(|d| d.path.as_path())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, [locked.as_path()]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn files_after_a_timeout_still_parse() {
+    // far too many functions to parse within the budget
+    let big = (0..100_000)
+        .map(|i| format!("def f{i}(x):\n    g{i}(x)\n\n"))
+        .collect::<String>();
+    let dir = project(
+        "timeout",
+        &[
+            ("a_big.py", &big),
+            ("b_small.py", "def main():\n    run()\n"),
+        ],
+    );
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    // one thread, so the small file reuses the parser the big one left off with
+    draveur
+        .threads(1)
+        .fail_fast(false)
+        .timeout(Duration::from_millis(50));
+
+    let analysis = draveur.analyze(&dir.to_string_lossy()).unwrap();
+    let names = analysis
+        .graphs
+        .iter()
+        .flat_map(|g| g.iter())
+        .filter_map(|n| n.name())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["main", "run"]);
+    assert_eq!(analysis.diagnostics.len(), 1);
+    assert!(matches!(
+        analysis.diagnostics[0].error,
+        Error::Timeout { .. }
+    ));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn graphs_come_in_file_then_source_then_rule_order() {
+    let dir = project(
+        "order",
+        &[
+            (
+                "b.py",
+                "def f():\n    pass\n\n@dataclass\nclass B:\n    def m(self):\n        pass\n",
+            ),
+            (
+                "a.py",
+                "@dataclass\nclass A:\n    pass\n\ndef g():\n    h()\n",
+            ),
+            ("pkg/c.py", "def c():\n    pass\n"),
+            ("pkg/a.py", "def d():\n    pass\n"),
+        ],
+    );
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    // a third rule matching the same definitions as the functions one
+    draveur
+        .add(
+            "(function_definition) @fn".into(),
+            r#"
+global global_filename
+(function_definition name: (identifier) @name) @fn
+{
+    node @fn.node
+    attr (@fn.node) name = (source-text @name), type = "extra"
+}
+"#
+            .into(),
+        )
+        .unwrap();
+
+    for threads in [1, 4] {
+        draveur.threads(threads);
+        let graphs = draveur.waltz(&dir.to_string_lossy()).unwrap();
+        let roots = graphs
+            .iter()
+            .filter_map(|g| g.root())
+            .map(|root| (root.name().unwrap(), root.node_type().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            roots,
+            [
+                ("A", "class_definition"),
+                ("g", "function_definition"),
+                ("g", "extra"),
+                ("f", "function_definition"),
+                ("f", "extra"),
+                ("B", "class_definition"),
+                // methods are part of their class' graph
+                ("m", "extra"),
+                ("d", "function_definition"),
+                ("d", "extra"),
+                ("c", "function_definition"),
+                ("c", "extra"),
+            ],
+            "with {threads} threads"
+        );
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn edges_point_at_their_nodes_across_threads() {
+    // graphs of several nodes, built by workers in parallel
+    let sources = (0..40)
+        .map(|i| {
+            let source =
+                format!("def f{i}(x):\n    if x:\n        g{i}(x)\n    else:\n        h{i}()\n");
+            (format!("m{i:02}.py"), source)
+        })
+        .collect::<Vec<_>>();
+    let files = sources
+        .iter()
+        .map(|(path, source)| (path.as_str(), source.as_str()))
+        .collect::<Vec<_>>();
+    let dir = project("edges", &files);
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.threads(4);
+
+    let graphs = draveur.waltz(&dir.to_string_lossy()).unwrap();
+    for graph in &graphs {
+        let ids = graph.ids();
+        let sinks = graph.iter().flat_map(|n| n.edges()).map(|e| e.sink());
+        assert!(
+            sinks.into_iter().all(|sink| ids.contains(sink)),
+            "{graph:?}"
+        );
+    }
+
+    // same nodes and edges as each file analyzed alone
+    let expected = sources
+        .iter()
+        .flat_map(|(path, source)| {
+            draveur
+                .analyze_source(dir.join(path), source.as_bytes())
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(normalize(&graphs), normalize(&expected));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn slow_stream_consumers_get_every_file() {
+    // many more files than the queue between workers and the consumer holds
+    let sources = (0..40)
+        .map(|i| (format!("m{i:02}.py"), format!("def f{i}():\n    g{i}()\n")))
+        .collect::<Vec<_>>();
+    let files = sources
+        .iter()
+        .map(|(path, source)| (path.as_str(), source.as_str()))
+        .collect::<Vec<_>>();
+    let dir = project("stream", &files);
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.threads(2);
+
+    let mut streamed = vec![];
+    let diagnostics = draveur
+        .stream(&dir.to_string_lossy(), |path, graphs| {
+            // workers fill the queue while this one is busy
+            thread::sleep(Duration::from_millis(5));
+            streamed.push((path, graphs.len()));
+        })
+        .unwrap();
+
+    assert!(diagnostics.is_empty());
+    streamed.sort();
+    let expected = sources
+        .iter()
+        .map(|(path, _)| (dir.join(path), 1))
+        .collect::<Vec<(PathBuf, usize)>>();
+    assert_eq!(streamed, expected);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn panicking_stream_consumers_stop_the_crawl() {
+    // many more files than the queue between workers and the consumer holds
+    let sources = (0..40)
+        .map(|i| (format!("m{i:02}.py"), format!("def f{i}():\n    g{i}()\n")))
+        .collect::<Vec<_>>();
+    let files = sources
+        .iter()
+        .map(|(path, source)| (path.as_str(), source.as_str()))
+        .collect::<Vec<_>>();
+    let dir = project("stream-panic", &files);
+
+    let (tx, rx) = mpsc::channel();
+    let root = dir.clone();
+    thread::spawn(move || {
+        let mut draveur = Python::draveur(&[], &[]).unwrap();
+        draveur.threads(2);
+        let mut seen = 0;
+        let streamed = panic::catch_unwind(AssertUnwindSafe(|| {
+            draveur.stream(&root.to_string_lossy(), |_, _| {
+                seen += 1;
+                if seen == 3 {
+                    panic!("consumer failed");
+                }
+            })
+        }));
+        tx.send(streamed.is_err()).unwrap();
+    });
+
+    // the panic reaches the caller instead of leaving the workers waiting on the queue
+    let panicked = rx.recv_timeout(Duration::from_secs(30)).unwrap();
+    assert!(panicked);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,75 @@
+use draveur::Error;
+use draveur::document::{Document, FORMAT_VERSION};
+use draveur::resolve::resolve_calls;
+use draveur::testing::RuleTest;
+use draveur_python::Python;
+
+const SOURCE: &str = r#"
+def load(path: str) -> bytes:
+    return read(path)
+
+def main():
+    data = load("x")
+    if data:
+        store(data)
+"#;
+
+#[test]
+fn round_trip() {
+    let test = RuleTest::with(Python::draveur(&[], &[]).unwrap());
+    let mut graphs = test.run(SOURCE).unwrap();
+    resolve_calls(&mut graphs);
+
+    let mut bytes = vec![];
+    Document::new(&graphs).write(&mut bytes).unwrap();
+    let read = Document::read(bytes.as_slice()).unwrap();
+    assert_eq!(read.version, FORMAT_VERSION);
+    assert_eq!(read.into_graphs(), graphs);
+}
+
+#[test]
+fn rejects_other_versions() {
+    let document = r#"{"version": 2, "graphs": []}"#;
+    let error = Document::read(document.as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::UnsupportedVersion(Some(2))));
+}
+
+#[test]
+fn rejects_dangling_edges() {
+    let document = r#"{"version": 1, "graphs": [[
+        {"id": 1, "edges": [{"sink": 2, "attrs": {}}], "attrs": {}}
+    ]]}"#;
+    let error = Document::read(document.as_bytes()).err().unwrap();
+    assert!(matches!(error, Error::DanglingEdge { node: 1, sink: 2 }));
+}
+
+#[test]
+fn rejects_ids_shared_across_graphs() {
+    let document = r#"{"version": 1, "graphs": [
+        [{"id": 1, "edges": [], "attrs": {}}],
+        [{"id": 1, "edges": [], "attrs": {}}]
+    ]}"#;
+    let error = Document::read(document.as_bytes()).err().unwrap();
+    assert_eq!(error.to_string(), "node id 1 is used by more than one node");
+}
+
+#[test]
+fn reads_tagged_values() {
+    // attribute values as earlier versions wrote them
+    let document = r#"{"version": 1, "graphs": [[
+        {"id": 1, "edges": [], "attrs": {
+            "name": {"type": "string", "string": "main"},
+            "start_row": {"type": "int", "int": 3},
+            "async": {"type": "bool", "bool": false},
+            "doc": {"type": "null"},
+            "args": {"type": "list", "list": [{"type": "string", "string": "path"}, "retries"]}
+        }}
+    ]]}"#;
+    let graphs = Document::read(document.as_bytes()).unwrap().into_graphs();
+    let plain = serde_json::json!([[
+        {"id": 1, "edges": [], "attrs": {
+            "name": "main", "start_row": 3, "async": false, "doc": null, "args": ["path", "retries"]
+        }}
+    ]]);
+    assert_eq!(serde_json::to_value(&graphs).unwrap(), plain);
+}
@@ -0,0 +1,6 @@
+; functions decorated with @task
+(decorated_definition
+    ;; @include decorator
+    definition: (function_definition)
+    (#eq? @decorator_name "task")
+) @task
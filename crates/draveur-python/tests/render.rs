@@ -0,0 +1,160 @@
+use draveur::Graph;
+use draveur::render::{Ascii, Dot, Mermaid, Render, Svg};
+use draveur::resolve::resolve_calls;
+use draveur::testing::{RuleTest, assert_snapshot};
+use draveur_python::Python;
+
+macro_rules! snapshot {
+    ($name:literal) => {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/", $name)
+    };
+}
+
+const JOBS: &str = r#"
+@dataclass
+class Job:
+    def run(self):
+        self.prepare()
+        submit(self)
+
+    def prepare(self):
+        pass
+"#;
+
+const FLOWS: &str = r#"
+def submit(job):
+    if job.ready:
+        send(job)
+    elif job.retries > 3:
+        drop(job)
+    else:
+        retry(job)
+"#;
+
+// graphs of both files with their calls resolved across them
+fn graphs() -> Vec<Graph> {
+    let draveur = || Python::draveur(&[], &[]).unwrap();
+    let mut graphs = RuleTest::with(draveur()).path("jobs.py").run(JOBS).unwrap();
+    graphs.extend(
+        RuleTest::with(draveur())
+            .path("flows.py")
+            .run(FLOWS)
+            .unwrap(),
+    );
+    resolve_calls(&mut graphs);
+    graphs
+}
+
+#[test]
+fn mermaid() {
+    assert_snapshot(
+        &Mermaid::default().to_string(&graphs()),
+        snapshot!("render.mmd"),
+    );
+}
+
+#[test]
+fn dot() {
+    assert_snapshot(
+        &Dot::default().to_string(&graphs()),
+        snapshot!("render.dot"),
+    );
+}
+
+#[test]
+fn ascii() {
+    assert_snapshot(
+        &Ascii::default().to_string(&graphs()),
+        snapshot!("render.txt"),
+    );
+}
+
+#[test]
+fn ascii_crossings_are_marked() {
+    let source = r#"
+@dataclass
+class Pipeline:
+    def load(self):
+        self.save()
+        self.clean()
+
+    def clean(self):
+        pass
+
+    def save(self):
+        self.clean()
+"#;
+    let mut graphs = RuleTest::with(Python::draveur(&[], &[]).unwrap())
+        .path("pipeline.py")
+        .run(source)
+        .unwrap();
+    resolve_calls(&mut graphs);
+
+    let diagram = Ascii::default().to_string(&graphs);
+    assert!(diagram.contains('╫'), "{diagram}");
+    assert_snapshot(&diagram, snapshot!("render_crossing.txt"));
+}
+
+#[test]
+fn ascii_labels_are_truncated() {
+    let source = "def a_function_with_a_rather_long_name():\n    another_quite_long_function_name()\n    short()\n";
+    let graphs = RuleTest::with(Python::draveur(&[], &[]).unwrap())
+        .run(source)
+        .unwrap();
+
+    // down to the longest label allowed
+    let ascii = Ascii {
+        max_label: 12,
+        ..Default::default()
+    };
+    let diagram = ascii.to_string(&graphs);
+    assert!(diagram.contains("a_function_…"), "{diagram}");
+    assert!(diagram.contains("another_qui…"), "{diagram}");
+    assert!(diagram.contains("short"), "{diagram}");
+
+    // further down for the diagram to fit
+    let ascii = Ascii {
+        max_width: 30,
+        ..Default::default()
+    };
+    let diagram = ascii.to_string(&graphs);
+    let boxes = diagram.lines().skip(1);
+    assert!(
+        boxes.clone().all(|line| line.chars().count() <= 30),
+        "{diagram}"
+    );
+    assert!(boxes.clone().any(|line| line.contains('…')), "{diagram}");
+}
+
+#[test]
+fn svg() {
+    assert_snapshot(
+        &Svg::default().to_string(&graphs()),
+        snapshot!("render.svg"),
+    );
+}
+
+#[test]
+fn ascii_edges_without_kind_have_arrowheads() {
+    let test = RuleTest::<Python>::new(
+        "(function_definition) @fn",
+        r#"
+(function_definition name: (identifier) @name) @fn
+{
+    node @fn.node
+    attr (@fn.node) name = (source-text @name)
+}
+(function_definition body: (block (expression_statement (call function: (identifier) @callee) @call))) @fn
+{
+    node @call.node
+    attr (@call.node) name = (source-text @callee)
+    edge @fn.node -> @call.node
+}
+"#,
+    )
+    .unwrap();
+    let graphs = test.run("def a():\n    b()\n").unwrap();
+
+    let diagram = Ascii::default().to_string(&graphs);
+    assert!(diagram.contains('▼'), "{diagram}");
+}
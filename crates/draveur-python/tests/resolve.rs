@@ -0,0 +1,149 @@
+use draveur::resolve::{RESOLVES_TO, resolve_calls};
+use draveur::testing::RuleTest;
+use draveur::traverse::{Edges, Traversal};
+use draveur::{Graph, Node};
+use draveur_python::Python;
+
+// graphs of `(path, source)` files with their calls resolved across them
+fn resolve(files: &[(&str, &str)]) -> Vec<Graph> {
+    let mut graphs = vec![];
+    for (path, source) in files {
+        let draveur = Python::draveur(&[], &[]).unwrap();
+        graphs.extend(RuleTest::with(draveur).path(path).run(source).unwrap());
+    }
+    resolve_calls(&mut graphs);
+    graphs
+}
+
+// `call -> file:definition` for each call resolved in `graphs`, in source order
+fn links(graphs: &[Graph]) -> Vec<String> {
+    let traversal = Traversal::new(graphs);
+    let file = |node: &Node| {
+        let graph = graphs
+            .iter()
+            .find(|g| g.iter().any(|n| n.id() == node.id()));
+        let root = graph.and_then(Graph::root).unwrap();
+        root.get("filename").unwrap().as_str().unwrap().to_string()
+    };
+
+    traversal
+        .nodes()
+        .filter(|node| node.node_type() == Some("call"))
+        .flat_map(|call| {
+            traversal
+                .successors(call.id(), Edges::Kinds(&[RESOLVES_TO]))
+                .map(|def| {
+                    format!(
+                        "{} -> {}:{}",
+                        call.name().unwrap(),
+                        file(def),
+                        def.name().unwrap()
+                    )
+                })
+        })
+        .collect()
+}
+
+#[test]
+fn same_file_definitions_are_preferred() {
+    let graphs = resolve(&[
+        (
+            "a.py",
+            "def helper():\n    pass\n\ndef run():\n    helper()\n",
+        ),
+        ("b.py", "def helper():\n    pass\n"),
+    ]);
+    assert_eq!(links(&graphs), ["helper -> a.py:helper"]);
+}
+
+#[test]
+fn calls_resolve_across_files() {
+    let graphs = resolve(&[
+        ("app.py", "def main():\n    load()\n"),
+        ("storage.py", "def load():\n    pass\n"),
+        ("cache.py", "def load():\n    pass\n"),
+    ]);
+    assert_eq!(
+        links(&graphs),
+        ["load -> storage.py:load", "load -> cache.py:load"]
+    );
+}
+
+#[test]
+fn qualified_calls_resolve_to_their_module() {
+    let graphs = resolve(&[
+        (
+            "app.py",
+            "def main():\n    storage.load()\n    pkg.cache.load()\n    requests.get()\n    os.path.join()\n",
+        ),
+        ("storage.py", "def load():\n    pass\n"),
+        ("pkg/cache/__init__.py", "def load():\n    pass\n"),
+        ("http.py", "def get():\n    pass\n\ndef join():\n    pass\n"),
+    ]);
+    assert_eq!(
+        links(&graphs),
+        [
+            "storage.load -> storage.py:load",
+            "pkg.cache.load -> pkg/cache/__init__.py:load",
+        ]
+    );
+}
+
+#[test]
+fn methods_resolve_within_their_class() {
+    let graphs = resolve(&[(
+        "jobs.py",
+        r#"
+@dataclass
+class Job:
+    def run(self):
+        self.prepare()
+
+    @classmethod
+    def create(cls):
+        cls.prepare()
+
+    def prepare(self):
+        pass
+
+@dataclass
+class Other:
+    def prepare(self):
+        pass
+"#,
+    )]);
+    assert_eq!(
+        links(&graphs),
+        [
+            "self.prepare -> jobs.py:prepare",
+            "cls.prepare -> jobs.py:prepare",
+        ]
+    );
+
+    // both calls go to `Job.prepare`, not `Other.prepare`
+    let traversal = Traversal::new(&graphs);
+    let sinks = traversal
+        .nodes()
+        .filter(|node| node.node_type() == Some("call"))
+        .flat_map(|call| traversal.successors(call.id(), Edges::Kinds(&[RESOLVES_TO])))
+        .map(Node::id)
+        .collect::<Vec<_>>();
+    let job = traversal.named("prepare").next().unwrap();
+    assert_eq!(sinks, [job.id(), job.id()]);
+}
+
+#[test]
+fn recursive_calls_resolve_to_their_caller() {
+    let graphs = resolve(&[("tree.py", "def walk(node):\n    walk(node.child)\n")]);
+    assert_eq!(links(&graphs), ["walk -> tree.py:walk"]);
+
+    // the call keeps its `_parent` edge to the same function
+    let traversal = Traversal::new(&graphs);
+    let call = traversal.named("walk").nth(1).unwrap();
+    let kinds = call
+        .edges()
+        .iter()
+        .map(|edge| edge.kind().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["_parent", RESOLVES_TO]);
+}
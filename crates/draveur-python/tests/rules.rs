@@ -0,0 +1,422 @@
+mod common;
+
+use common::project;
+use draveur::testing::RuleTest;
+use draveur::{Error, TreeSitterError, rules};
+use draveur_python::{Python, functions_stanzas, query_functions};
+use serde_json::json;
+use std::fs;
+
+macro_rules! snapshot {
+    ($name:literal) => {
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/snapshots/",
+            $name,
+            ".json"
+        )
+    };
+}
+
+macro_rules! fixture {
+    ($name:literal) => {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/", $name)
+    };
+}
+
+const WORKFLOW: &str = r#"
+from workflows import activity, workflow
+
+@activity
+async def fetch(url: str) -> bytes:
+    return await http.get(url)
+
+def helper(x):
+    if x:
+        log(x)
+    elif x is None:
+        warn()
+    else:
+        fail()
+
+def main(path: str, retries=3) -> int:
+    order = load(path)
+    await submit(order)
+    return 0
+
+@workflow.define
+class Order:
+    def __init__(self, id: int):
+        self.id = id
+
+    @workflow.run
+    async def run(self, url: str) -> None:
+        data = await fetch(url)
+        with lock():
+            helper(data)
+"#;
+
+#[test]
+fn functions() {
+    let test = RuleTest::<Python>::new(query_functions!(), functions_stanzas!()).unwrap();
+    test.assert_snapshot(WORKFLOW, snapshot!("functions"));
+}
+
+#[test]
+fn classes() {
+    let draveur = Python::from_config(&draveur::config::Config {
+        rules: Some(vec!["classes".into()]),
+        ..Default::default()
+    })
+    .unwrap();
+    RuleTest::with(draveur).assert_snapshot(WORKFLOW, snapshot!("classes"));
+}
+
+#[test]
+fn decorator_allowlist() {
+    let draveur = Python::draveur(&["other".into()], &["activity".into()]).unwrap();
+    let source = "@activity\ndef fetch():\n    pass\n\n@other\nclass A:\n    pass\n\n@skipped\nclass B:\n    pass\n";
+    RuleTest::with(draveur).assert_graphs(
+        source,
+        json!([
+            [{
+                "id": 0,
+                "edges": [],
+                "attrs": {
+                    "decorator": "activity",
+                    "end_col": 8,
+                    "end_row": 2,
+                    "filename": "snippet.py",
+                    "name": "fetch",
+                    "src": "def fetch():\n    pass",
+                    "start_col": 0,
+                    "start_row": 1,
+                    "type": "function_definition"
+                }
+            }],
+            [{
+                "id": 0,
+                "edges": [],
+                "attrs": {
+                    "decorator": "other",
+                    "end_col": 8,
+                    "end_row": 6,
+                    "filename": "snippet.py",
+                    "name": "A",
+                    "src": "class A:\n    pass",
+                    "start_col": 0,
+                    "start_row": 5,
+                    "type": "class_definition"
+                }
+            }]
+        ]),
+    );
+}
+
+#[test]
+fn decorator_allowlists_keep_module_functions() {
+    // like without an allowlist, methods and nested functions are left out
+    let source = r#"
+@activity
+def fetch():
+    @activity
+    def inner():
+        pass
+
+def main():
+    def helper():
+        pass
+
+class A:
+    @activity
+    def run(self):
+        pass
+
+    def stop(self):
+        pass
+"#;
+    let names = |functions: &[&str]| {
+        let draveur = Python::from_config(&draveur::config::Config {
+            rules: Some(vec!["functions".into()]),
+            function_decorators: functions.iter().map(|f| f.to_string()).collect(),
+            ..Default::default()
+        })
+        .unwrap();
+        RuleTest::with(draveur)
+            .run(source)
+            .unwrap()
+            .iter()
+            .filter_map(|graph| Some(graph.root()?.name()?.to_string()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&[]), ["main"]);
+    assert_eq!(names(&["activity"]), ["fetch"]);
+}
+
+#[test]
+fn decorator_names_are_matched_whole() {
+    // names that would split into several or inject query syntax if spliced as is
+    let hostile = [
+        "activity other",
+        "x\") (#eq? @decorator_name \"activity",
+        "(activity)",
+        "activity\\",
+    ]
+    .map(String::from);
+    let draveur = Python::draveur(&hostile, &hostile).unwrap();
+    let source = "@activity\ndef fetch():\n    pass\n\n@other\nclass A:\n    pass\n";
+    RuleTest::with(draveur).assert_graphs(source, json!([]));
+}
+
+#[test]
+fn nested_positions() {
+    // positions stay relative to the file, not to the decorated definition
+    let source = "class A:\n    @deco\n    def f(self):\n        g()\n";
+    let test = RuleTest::<Python>::new(
+        "(decorated_definition) @fn",
+        r#"
+global global_filename
+(decorated_definition definition: (function_definition name: (identifier) @name) @fn)
+{
+    node @fn.node
+    attr (@fn.node) name = (source-text @name), row = (start-row @fn), col = (start-column @fn)
+}
+"#,
+    )
+    .unwrap();
+    test.assert_graphs(
+        source,
+        json!([[{"id": 0, "edges": [], "attrs": {"name": "f", "row": 2, "col": 4}}]]),
+    );
+}
+
+#[test]
+fn offset_positions_stay_in_the_file() {
+    // stanzas written when matches were reparsed on their own still add the offsets
+    let source = "class A:\n    @deco\n    def f(self):\n        g()\n";
+    let test = RuleTest::<Python>::new(
+        "(decorated_definition) @fn",
+        r#"
+global global_row
+global global_column
+(decorated_definition definition: (function_definition name: (identifier) @name) @fn)
+{
+    node @fn.node
+    attr (@fn.node) name = (source-text @name)
+    attr (@fn.node) row = (plus global_row (start-row @fn)), col = (plus global_column (start-column @fn))
+}
+"#,
+    )
+    .unwrap();
+    test.assert_graphs(
+        source,
+        json!([[{"id": 0, "edges": [], "attrs": {"name": "f", "row": 2, "col": 4}}]]),
+    );
+}
+
+#[test]
+fn lists_and_sets_convert_to_arrays() {
+    let test = RuleTest::<Python>::new(
+        "(function_definition) @fn",
+        r#"
+(function_definition name: (identifier) @name) @fn
+{
+    node @fn.node
+    attr (@fn.node) name = (source-text @name), list = ["b", "a", ["c"]], set = {"b", "a"}
+}
+"#,
+    )
+    .unwrap();
+    // sets come out in order
+    test.assert_graphs(
+        "def f():\n    pass\n",
+        json!([[{"id": 0, "edges": [], "attrs": {
+            "name": "f", "list": ["b", "a", ["c"]], "set": ["a", "b"]
+        }}]]),
+    );
+}
+
+#[test]
+fn syntax_node_attributes_are_rejected() {
+    let test = RuleTest::<Python>::new(
+        "(function_definition) @fn",
+        r#"
+(function_definition) @fn
+{
+    node @fn.node
+    attr (@fn.node) syntax = @fn
+}
+"#,
+    )
+    .unwrap();
+    let error = test.run("def f():\n    pass\n").unwrap_err();
+    assert!(
+        error.to_string().contains("unsupported attribute value"),
+        "{error}"
+    );
+}
+
+fn tasks() -> RuleTest<Python> {
+    let query = rules::read::<Python>(fixture!("tasks.scm")).unwrap();
+    let stanzas = rules::read::<Python>(fixture!("tasks.tsg")).unwrap();
+    RuleTest::<Python>::new(query, stanzas)
+        .unwrap()
+        .path("flows/tasks.py")
+}
+
+#[test]
+fn rule_files() {
+    let test = tasks();
+    let source = "@task\ndef load(path):\n    data = read(path)\n    with lock():\n        store(data)\n\n@other\ndef skipped():\n    pass\n";
+    test.assert_snapshot(source, snapshot!("rule_files"));
+}
+
+#[test]
+fn unmatched_code_is_not_executed() {
+    // the calls stanza needs the node only matched functions get
+    let source = "@task\ndef load(path):\n    data = read(path)\n\ndef plain():\n    go()\n";
+    let graphs = tasks().run(source).unwrap();
+    let names = graphs
+        .iter()
+        .map(|graph| {
+            graph
+                .iter()
+                .filter_map(|node| node.name())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, [["load", "read"]]);
+}
+
+#[test]
+fn matches_each_get_their_graph() {
+    // indented, nested and one-line matches with unmatched code in between
+    let source = "class A:\n    x = 1\n    def m(self):\n        a()\n        def inner():\n            b()\n\n    def n(self): c()\n\nprint(1)\ndef top():\n    d()\n";
+    let test = RuleTest::<Python>::new("(function_definition) @fn", functions_stanzas!()).unwrap();
+    let graphs = test.run(source).unwrap();
+    let names = graphs
+        .iter()
+        .map(|graph| {
+            graph
+                .iter()
+                .filter_map(|node| node.name())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            vec!["m", "a", "inner", "b"],
+            vec!["inner", "b"],
+            vec!["n", "c"],
+            vec!["top", "d"],
+        ]
+    );
+}
+
+#[test]
+fn failures_report_the_failing_match() {
+    // only `bad` lacks the node its attribute is set on
+    let test = RuleTest::<Python>::new(
+        "(function_definition) @fn",
+        r#"
+(function_definition) @fn
+{
+    node @fn.node
+}
+(function_definition name: (identifier) @name (#eq? @name "bad"))
+{
+    attr (@name.node) name = (source-text @name)
+}
+"#,
+    )
+    .unwrap();
+    let source = "def good():\n    pass\n\ndef bad():\n    pass\n";
+
+    let Err(Error::TreeSitter(TreeSitterError::Execution(failure))) = test.run(source) else {
+        panic!("expected an execution failure");
+    };
+    assert_eq!((failure.start.row, failure.end.row), (3, 4));
+    assert_eq!(failure.snippet, "def bad():\n    pass");
+}
+
+#[test]
+fn failures_name_the_rule_file() {
+    let dir = project(
+        "failing",
+        &[
+            ("bad.scm", "(function_definition) @fn\n"),
+            (
+                "bad.tsg",
+                "(function_definition name: (identifier) @name)\n{\n    attr (@name.node) name = (source-text @name)\n}\n",
+            ),
+        ],
+    );
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur
+        .load(dir.join("bad.scm"), dir.join("bad.tsg"))
+        .unwrap();
+
+    let error = RuleTest::with(draveur)
+        .run("def f():\n    pass\n")
+        .unwrap_err();
+    let message = error.to_string();
+    // after the functions and classes rule sets
+    let expected = format!(
+        "failed to execute rule 2 ({})",
+        dir.join("bad.tsg").display()
+    );
+    assert!(message.starts_with(&expected), "{message}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn include_cycles_are_rejected() {
+    let dir = project(
+        "cycle",
+        &[
+            ("a.tsg", ";; @include b.tsg\n"),
+            ("b.tsg", ";; @include a.tsg\n"),
+        ],
+    );
+    let error = rules::read::<Python>(dir.join("a.tsg")).err();
+    match error {
+        Some(Error::IncludeCycle { file }) => assert!(file.ends_with("a.tsg"), "{file}"),
+        error => panic!("expected an include cycle, got {error:?}"),
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn files_can_be_included_twice() {
+    // not a cycle, both halves include the same attributes
+    let dir = project(
+        "diamond",
+        &[
+            ("rule.tsg", ";; @include left.tsg\n;; @include right.tsg\n"),
+            ("left.tsg", ";; @include shared.tsg\n"),
+            ("right.tsg", ";; @include shared.tsg\n"),
+            ("shared.tsg", "attr (@fn.node) kind = \"shared\""),
+        ],
+    );
+    let rule = rules::read::<Python>(dir.join("rule.tsg")).unwrap();
+    assert_eq!(rule.matches("kind = \"shared\"").count(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unknown_fragments_are_rejected() {
+    let dir = project(
+        "fragment",
+        &[("rule.scm", "(function_definition)\n;; @include nope\n")],
+    );
+    let error = rules::read::<Python>(dir.join("rule.scm")).err();
+    match error {
+        Some(Error::UnknownFragment { file, name }) => {
+            assert!(file.ends_with("rule.scm"), "{file}");
+            assert_eq!(name, "nope");
+        }
+        error => panic!("expected an unknown fragment, got {error:?}"),
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::project;
+use draveur::cancel::CancellationToken;
+use draveur_python::{Python, server};
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+// response head, read byte by byte so nothing after it gets buffered away
+fn head(mut stream: &TcpStream) -> String {
+    let mut head = vec![];
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+fn page(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn serves_until_cancelled() {
+    let dir = project("cancel", &[("app.py", "def main():\n    run()\n")]);
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.cancellation(token.clone());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::scope(|s| {
+        let served = s.spawn(|| server::serve(&draveur, std::slice::from_ref(&dir), listener));
+
+        let response = page(addr);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.contains("<html"));
+
+        // the upgrade header only comes with the second packet
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /ws HTTP/1.1\r\nHost: x\r\n")
+            .unwrap();
+        stream.flush().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        stream
+            .write_all(
+                b"Upgrade: websocket\r\nConnection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .unwrap();
+        let response = head(&stream);
+        assert!(response.starts_with("HTTP/1.1 101"), "{response}");
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+
+        let mut ws = WebSocket::from_raw_socket(&stream, Role::Client, None);
+        let Message::Text(text) = ws.read().unwrap() else {
+            panic!("expected a text message");
+        };
+        let msg = serde_json::from_str::<serde_json::Value>(&text).unwrap();
+        assert_eq!(msg["type"], "graph");
+
+        let start = Instant::now();
+        token.cancel();
+        served.join().unwrap().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(TcpStream::connect(addr).is_err());
+    });
+    fs::remove_dir_all(dir).unwrap();
+}
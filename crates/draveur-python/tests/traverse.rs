@@ -0,0 +1,161 @@
+use draveur::resolve::resolve_calls;
+use draveur::testing::RuleTest;
+use draveur::traverse::{Edges, Traversal};
+use draveur::{Error, Graph, Node};
+use draveur_python::Python;
+
+const STORAGE: &str = r#"
+def load(path):
+    data = read(path)
+    parsed = parse(data)
+    return parsed
+
+def parse(data):
+    text = decode(data)
+    return text
+"#;
+
+const APP: &str = r#"
+def main():
+    config = load("app.toml")
+    start(config)
+
+def ping(n):
+    pong(n)
+
+def pong(n):
+    ping(n)
+"#;
+
+// graphs of both files with their calls resolved across them
+fn graphs() -> Vec<Graph> {
+    let draveur = || Python::draveur(&[], &[]).unwrap();
+    let mut graphs = RuleTest::with(draveur())
+        .path("storage.py")
+        .run(STORAGE)
+        .unwrap();
+    let app = RuleTest::with(draveur()).path("app.py").run(APP).unwrap();
+    graphs.extend(app);
+    resolve_calls(&mut graphs);
+    graphs
+}
+
+fn names<'g>(nodes: impl IntoIterator<Item = &'g Node>) -> Vec<String> {
+    nodes
+        .into_iter()
+        .map(|node| format!("{}:{}", node.node_type().unwrap(), node.name().unwrap()))
+        .collect()
+}
+
+fn function<'g>(traversal: &Traversal<'g>, name: &str) -> &'g Node {
+    traversal
+        .named(name)
+        .find(|node| node.node_type() == Some("function_definition"))
+        .unwrap()
+}
+
+#[test]
+fn neighbours() {
+    let graphs = graphs();
+    let traversal = Traversal::new(&graphs);
+    let load = function(&traversal, "load");
+
+    let calls = traversal.successors(load.id(), Edges::Kinds(&["call"]));
+    assert_eq!(names(calls), ["call:read", "call:parse"]);
+
+    let callers = traversal.predecessors(load.id(), Edges::Kinds(&["resolves_to"]));
+    assert_eq!(names(callers), ["call:load"]);
+
+    // `_parent` back-edges are only followed on demand
+    let first = traversal
+        .successors(load.id(), Edges::Forward)
+        .next()
+        .unwrap();
+    assert_eq!(traversal.successors(first.id(), Edges::Forward).count(), 0);
+    assert_eq!(
+        names(traversal.successors(first.id(), Edges::All)),
+        ["function_definition:load"]
+    );
+}
+
+#[test]
+fn walks() {
+    let graphs = graphs();
+    let traversal = Traversal::new(&graphs);
+    let main = function(&traversal, "main");
+
+    assert_eq!(
+        names(traversal.bfs(main.id(), Edges::Forward)),
+        [
+            "function_definition:main",
+            "call:load",
+            "call:start",
+            "function_definition:load",
+            "call:read",
+            "call:parse",
+            "function_definition:parse",
+            "call:decode",
+        ]
+    );
+    assert_eq!(
+        names(traversal.dfs(main.id(), Edges::Forward)),
+        [
+            "function_definition:main",
+            "call:load",
+            "function_definition:load",
+            "call:read",
+            "call:parse",
+            "function_definition:parse",
+            "call:decode",
+            "call:start",
+        ]
+    );
+
+    let parse = function(&traversal, "parse");
+    assert!(traversal.is_reachable(main.id(), parse.id(), Edges::Forward));
+    assert!(!traversal.is_reachable(parse.id(), main.id(), Edges::Forward));
+    assert_eq!(traversal.reachable(parse.id(), Edges::Forward).len(), 2);
+
+    // a single graph knows nothing of the calls resolved to other files
+    let single = graphs.iter().find(|g| g.root() == Some(main)).unwrap();
+    assert_eq!(single.traversal().bfs(main.id(), Edges::Forward).count(), 3);
+}
+
+#[test]
+fn shortest_path() {
+    let graphs = graphs();
+    let traversal = Traversal::new(&graphs);
+
+    let path = traversal.shortest_path("main", "decode", Edges::Forward);
+    assert_eq!(
+        names(path.unwrap()),
+        [
+            "function_definition:main",
+            "call:load",
+            "function_definition:load",
+            "call:parse",
+            "function_definition:parse",
+            "call:decode",
+        ]
+    );
+    assert!(
+        traversal
+            .shortest_path("decode", "main", Edges::Forward)
+            .is_none()
+    );
+}
+
+#[test]
+fn topological_sort() {
+    let graphs = graphs();
+    let storage = Traversal::new(&graphs[..2]);
+    let sorted = names(storage.topological_sort(Edges::Forward).unwrap());
+    let position = |name: &str| sorted.iter().position(|n| n == name).unwrap();
+    assert!(position("call:parse") < position("function_definition:parse"));
+    assert!(position("function_definition:load") < position("call:parse"));
+
+    // `ping` and `pong` call each other
+    let traversal = Traversal::new(&graphs);
+    let error = traversal.topological_sort(Edges::Forward).err().unwrap();
+    assert!(matches!(error, Error::Cycle(_)));
+}
@@ -0,0 +1,199 @@
+mod common;
+
+use common::project;
+use draveur::Graph;
+use draveur::cancel::CancellationToken;
+use draveur::draveur::Draveur;
+use draveur::watch::{Delta, Watch};
+use draveur_python::Python;
+use std::fs;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn cancelling_ends_the_watch() {
+    let dir = project("cancel", &[("app.py", "def main():\n    run()\n")])
+        .canonicalize()
+        .unwrap();
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.cancellation(token.clone());
+
+    let mut watch = draveur.watch(&[&dir]).unwrap();
+    assert_eq!(watch.next().unwrap().unwrap().updated.len(), 1);
+
+    let start = Instant::now();
+    thread::scope(|s| {
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(200));
+            token.cancel();
+        });
+        // nothing changes, only the cancellation ends the wait
+        assert!(watch.next().is_none());
+    });
+    assert!(start.elapsed() < Duration::from_secs(5));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+// next delta of `watch`, or `None` if none comes before `wait` is over
+fn next_within(
+    watch: &mut Watch<'_, Python>,
+    token: &CancellationToken,
+    wait: Duration,
+) -> Option<Delta> {
+    let (done, finished) = mpsc::channel::<()>();
+    let delta = thread::scope(|s| {
+        s.spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(wait) {
+                token.cancel();
+            }
+        });
+        let delta = watch.next().map(Result::unwrap);
+        drop(done);
+        delta
+    });
+    // a wait that ran out cancelled the token, let the next one start afresh
+    token.reset();
+    delta
+}
+
+#[test]
+fn writes_in_a_burst_make_one_delta() {
+    let dir = project("burst", &[("app.py", "def main():\n    run()\n")])
+        .canonicalize()
+        .unwrap();
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.cancellation(token.clone());
+    let mut watch = draveur.watch(&[&dir]).unwrap();
+    watch.next().unwrap().unwrap();
+
+    // editors often write a file several times per save
+    for call in ["a", "b", "c"] {
+        fs::write(dir.join("app.py"), format!("def main():\n    {call}()\n")).unwrap();
+    }
+
+    let delta = next_within(&mut watch, &token, Duration::from_secs(2)).unwrap();
+    let updated = delta.updated.iter().map(|(p, _)| p).collect::<Vec<_>>();
+    assert_eq!(updated, [&dir.join("app.py")]);
+    let calls = watch
+        .graphs()
+        .flat_map(|g| g.iter())
+        .filter_map(|n| n.name());
+    assert!(calls.collect::<Vec<_>>().contains(&"c"));
+
+    assert!(next_within(&mut watch, &token, Duration::from_millis(500)).is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn gitignore_changes_rescan() {
+    let dir = project(
+        "gitignore",
+        &[
+            ("app.py", "def main():\n    run()\n"),
+            ("generated.py", "def build():\n    make()\n"),
+        ],
+    )
+    .canonicalize()
+    .unwrap();
+    // .gitignore files only apply within repositories
+    fs::create_dir(dir.join(".git")).unwrap();
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.cancellation(token.clone());
+    let mut watch = draveur.watch(&[&dir]).unwrap();
+    assert_eq!(watch.next().unwrap().unwrap().updated.len(), 2);
+
+    fs::write(dir.join(".gitignore"), "generated.py\n").unwrap();
+    let delta = next_within(&mut watch, &token, Duration::from_secs(2)).unwrap();
+    assert_eq!(delta.removed, [dir.join("generated.py")]);
+    assert_eq!(watch.files().len(), 1);
+
+    // and back once no longer ignored
+    fs::remove_file(dir.join(".gitignore")).unwrap();
+    let delta = next_within(&mut watch, &token, Duration::from_secs(2)).unwrap();
+    assert!(
+        delta
+            .updated
+            .iter()
+            .any(|(p, _)| *p == dir.join("generated.py"))
+    );
+    assert_eq!(watch.files().len(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn updated_files_keep_their_ids() {
+    let files = [
+        ("app.py", "def main():\n    run()\n"),
+        ("pkg/jobs.py", "def job():\n    work()\n"),
+    ];
+    let dir = project("ids", &files).canonicalize().unwrap();
+    let copy = project("ids-copy", &files);
+    let token = CancellationToken::new();
+    let mut draveur = Python::draveur(&[], &[]).unwrap();
+    draveur.cancellation(token.clone());
+    let ids = |graphs: &mut dyn Iterator<Item = &Graph>| {
+        graphs
+            .flat_map(|g| g.iter().map(|n| n.id()))
+            .collect::<Vec<_>>()
+    };
+
+    // the same ids as a crawl of the same code elsewhere
+    let analyzed = ids(&mut draveur.waltz(&copy.to_string_lossy()).unwrap().iter());
+    let mut watch = draveur.watch(&[&dir]).unwrap();
+    watch.next().unwrap().unwrap();
+    assert_eq!(ids(&mut watch.graphs()), analyzed);
+
+    // touching a file analyzes it alone, named as in the initial crawl
+    fs::write(dir.join("pkg/jobs.py"), files[1].1).unwrap();
+    next_within(&mut watch, &token, Duration::from_secs(2)).unwrap();
+    assert_eq!(ids(&mut watch.graphs()), analyzed);
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(copy).unwrap();
+}
+
+#[test]
+fn failures_last_until_fixed() {
+    let dir = project("failures", &[("app.py", "def main():\n    run()\n")])
+        .canonicalize()
+        .unwrap();
+    fs::write(dir.join("bad.py"), "def bad():\n    pass\n").unwrap();
+    let token = CancellationToken::new();
+    // functions named `bad` lack the node their attribute is set on, so they fail
+    let mut draveur = Draveur::<Python>::new();
+    draveur
+        .add(
+            "(function_definition) @fn".into(),
+            r#"
+(function_definition) @fn
+{
+    node @fn.node
+}
+(function_definition name: (identifier) @name (#eq? @name "bad"))
+{
+    attr (@name.node) name = (source-text @name)
+}
+"#
+            .into(),
+        )
+        .unwrap();
+    draveur.cancellation(token.clone()).fail_fast(false);
+    let mut watch = draveur.watch(&[&dir]).unwrap();
+    assert_eq!(watch.next().unwrap().unwrap().failed, [dir.join("bad.py")]);
+
+    // still failing after unrelated changes
+    fs::write(dir.join("app.py"), "def main():\n    stop()\n").unwrap();
+    let delta = next_within(&mut watch, &token, Duration::from_secs(2)).unwrap();
+    assert!(delta.failed.is_empty());
+    let failed = watch.diagnostics().keys().collect::<Vec<_>>();
+    assert_eq!(failed, [&dir.join("bad.py")]);
+
+    fs::write(dir.join("bad.py"), "def good():\n    pass\n").unwrap();
+    next_within(&mut watch, &token, Duration::from_secs(2)).unwrap();
+    assert!(watch.diagnostics().is_empty());
+    assert_eq!(watch.files().len(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
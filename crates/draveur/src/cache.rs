@@ -0,0 +1,76 @@
+//! On-disk cache of the subgraphs produced for each file.
+//!
+//! Every source path gets its own entry, reused only while both the hash of the file content
+//! and the hash of the rule set match, so editing either one invalidates it.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::hash::hash;
+use crate::types::Graph;
+use crate::{IoErrorKind, Result};
+
+// tells apart the temporary files of entries written at once by this process
+static WRITES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Deserialize)]
+struct Entry<'a> {
+    path: String,
+    content: u64,
+    rules: u64,
+    graphs: Cow<'a, [Graph]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| IoErrorKind::create(&dir, e))?;
+        Ok(Self { dir })
+    }
+
+    fn entry_path(&self, path: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", hash(path.as_bytes())))
+    }
+
+    /// Cached subgraphs of `path`, unreadable or outdated entries count as misses
+    pub fn get(&self, path: &Path, content: u64, rules: u64) -> Option<Vec<Graph>> {
+        let path = path.display().to_string();
+        let file = File::open(self.entry_path(&path)).ok()?;
+        let entry: Entry = serde_json::from_reader(BufReader::new(file)).ok()?;
+
+        (entry.path == path && entry.content == content && entry.rules == rules)
+            .then(|| entry.graphs.into_owned())
+    }
+
+    pub fn put(&self, path: &Path, content: u64, rules: u64, graphs: &[Graph]) -> Result<()> {
+        let entry = Entry {
+            path: path.display().to_string(),
+            content,
+            rules,
+            graphs: Cow::Borrowed(graphs),
+        };
+        let target = self.entry_path(&entry.path);
+
+        // write next to the entry and rename so concurrent runs never read half an entry
+        let write = WRITES.fetch_add(1, Ordering::Relaxed);
+        let tmp = target.with_extension(format!("{}.{write}.tmp", process::id()));
+        let file = File::create(&tmp).map_err(|e| IoErrorKind::create(&tmp, e))?;
+        let mut w = BufWriter::new(file);
+        serde_json::to_writer(&mut w, &entry)?;
+        w.flush().map_err(|e| IoErrorKind::write(&tmp, e))?;
+
+        fs::rename(&tmp, &target).map_err(|e| IoErrorKind::write(&target, e))?;
+        Ok(())
+    }
+}
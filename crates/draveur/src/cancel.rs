@@ -0,0 +1,78 @@
+//! Stopping crawls early, either from another thread or once a file runs out of time.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tree_sitter_graph::{CancellationError, CancellationFlag};
+
+/// Handle cancelling every crawl it was given to, clones share the same state
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Lets crawls started from now on run again
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+impl CancellationFlag for CancellationToken {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        match self.is_cancelled() {
+            true => Err(CancellationError(at)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Time left to process a single file
+pub(crate) struct Budget<'a> {
+    token: &'a CancellationToken,
+    deadline: Option<Instant>,
+}
+
+impl<'a> Budget<'a> {
+    pub fn new(token: &'a CancellationToken, limit: Option<Duration>) -> Self {
+        Self {
+            token,
+            deadline: limit.map(|limit| Instant::now() + limit),
+        }
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Time left as tree-sitter timeouts take it, 0 meaning no limit
+    pub fn timeout_micros(&self) -> u64 {
+        self.remaining()
+            .map_or(0, |left| left.as_micros().max(1) as u64)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining().is_some_and(|left| left.is_zero())
+    }
+}
+
+impl CancellationFlag for Budget<'_> {
+    fn check(&self, at: &'static str) -> Result<(), CancellationError> {
+        self.token.check(at)?;
+        match self.is_exhausted() {
+            true => Err(CancellationError(at)),
+            false => Ok(()),
+        }
+    }
+}
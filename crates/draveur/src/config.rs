@@ -0,0 +1,125 @@
+//! Project settings, read from `draveur.toml` or the `[tool.draveur]` table of
+//! `pyproject.toml` at the crawl root.
+//!
+//! ```toml
+//! class-decorators = ["workflows.workflow.define"]
+//! function-decorators = ["workflows.activity"]
+//! include = ["src/**"]
+//! exclude = ["tests/", "*_pb2.py"]
+//! rules = ["classes"]
+//! format = "json"
+//! threads = 4
+//!
+//! # rules of the project, on top of the enabled rule sets
+//! [[rule-files]]
+//! query = "rules/tasks.scm"
+//! stanzas = "rules/tasks.tsg"
+//! ```
+
+use serde::Deserialize;
+use serde::de::{self, DeserializeOwned};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, IoErrorKind, Result};
+
+pub const CONFIG_FILE: &str = "draveur.toml";
+pub const PYPROJECT_FILE: &str = "pyproject.toml";
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Only keep classes with one of these decorators, empty keeps every decorated class
+    pub class_decorators: Vec<String>,
+    /// Only keep module functions with one of these decorators, empty keeps every one
+    pub function_decorators: Vec<String>,
+    /// Gitignore-style globs relative to the crawl root, files must match one of them if any
+    pub include: Vec<String>,
+    /// Gitignore-style globs relative to the crawl root of files to skip
+    pub exclude: Vec<String>,
+    /// Names of the rule sets to run, all of them when unset
+    pub rules: Option<Vec<String>>,
+    /// Output format of the CLI, e.g. "json" or "mermaid"
+    pub format: Option<String>,
+    pub threads: Option<usize>,
+    /// Rules read from files, see [`crate::rules`]
+    pub rule_files: Vec<RuleFiles>,
+}
+
+/// Query and stanza files of a rule, relative to the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleFiles {
+    pub query: PathBuf,
+    pub stanzas: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Pyproject {
+    tool: Option<Tool>,
+}
+
+#[derive(Deserialize)]
+struct Tool {
+    draveur: Option<Config>,
+}
+
+impl Config {
+    /// Reads a `draveur.toml`-like file, or the `[tool.draveur]` table of a `pyproject.toml`
+    /// which fails without one
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        match path.file_name().is_some_and(|name| name == PYPROJECT_FILE) {
+            true => Self::pyproject(path)?.ok_or_else(|| Error::Config {
+                file: path.display().to_string(),
+                source: de::Error::custom("missing [tool.draveur] table"),
+            }),
+            false => Ok(parse::<Self>(path)?.relative_to(path)),
+        }
+    }
+
+    /// Config of the crawl rooted at `root`, `draveur.toml` taking precedence over
+    /// `pyproject.toml`. `None` if neither exists or the pyproject has no `[tool.draveur]`.
+    pub fn discover(root: impl AsRef<Path>) -> Result<Option<Self>> {
+        let root = root.as_ref();
+        // a single file is crawled from its directory
+        let dir = match root.is_file() {
+            true => root.parent().unwrap_or(Path::new(".")),
+            false => root,
+        };
+
+        let config = dir.join(CONFIG_FILE);
+        if config.is_file() {
+            return Ok(Some(parse::<Self>(&config)?.relative_to(&config)));
+        }
+        let pyproject = dir.join(PYPROJECT_FILE);
+        match pyproject.is_file() {
+            true => Self::pyproject(&pyproject),
+            false => Ok(None),
+        }
+    }
+
+    fn pyproject(path: &Path) -> Result<Option<Self>> {
+        let pyproject = parse::<Pyproject>(path)?;
+        let config = pyproject.tool.and_then(|tool| tool.draveur);
+        Ok(config.map(|config| config.relative_to(path)))
+    }
+
+    // rule files are relative to the config file, not to where draveur runs
+    fn relative_to(mut self, file: &Path) -> Self {
+        let dir = file.parent().unwrap_or(Path::new(""));
+        for rule in &mut self.rule_files {
+            rule.query = dir.join(&rule.query);
+            rule.stanzas = dir.join(&rule.stanzas);
+        }
+        self
+    }
+}
+
+fn parse<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let text = fs::read_to_string(path).map_err(|e| IoErrorKind::read(path, e))?;
+    toml::from_str(&text).map_err(|source| Error::Config {
+        file: path.display().to_string(),
+        source,
+    })
+}
@@ -1,13 +1,14 @@
 //! Traverses file directory ignoring globbed patterns in .gitignore
 //! use ignore Walker with configurable threads
 
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::{DirEntry, WalkBuilder, WalkState};
-use std::cell::OnceCell;
-use std::path::PathBuf;
-use std::sync::{Arc};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
-use crate::Error;
 use crate::lang::Lang;
+use crate::{Diagnostic, Error};
 
 pub trait Visitor: Send + Sync {
     type Item;
@@ -27,6 +28,15 @@ pub(crate) struct CrawlOpts {
     pub dir: PathBuf,
     pub threads: usize,
     pub allowed_exts: Vec<String>,
+    // stop at the first failing file instead of collecting every failure
+    pub fail_fast: bool,
+    // gitignore-style globs relative to `dir`, on top of the standard filters
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // directory file paths are made relative to for node ids, `dir` or its parent by default
+    pub base: Option<PathBuf>,
+    // prepended to the names of files, tells apart files crawled from different paths
+    pub prefix: String,
 }
 
 impl CrawlOpts {
@@ -38,6 +48,23 @@ impl CrawlOpts {
         self.threads = threads;
         self
     }
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+    pub fn globs(mut self, include: &[String], exclude: &[String]) -> Self {
+        self.include = include.to_vec();
+        self.exclude = exclude.to_vec();
+        self
+    }
+    pub fn base<P: Into<PathBuf>>(mut self, base: P) -> Self {
+        self.base = Some(base.into());
+        self
+    }
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
     pub fn add_lang<L: Lang>(mut self) -> Self {
         self.allowed_exts.push(L::EXT.to_string());
         self
@@ -53,89 +80,302 @@ impl Default for CrawlOpts {
             dir: "./".into(),
             threads: 0,
             allowed_exts: vec![],
+            fail_fast: true,
+            include: vec![],
+            exclude: vec![],
+            base: None,
+            prefix: String::new(),
         }
     }
 }
 
-#[derive(Clone)]
-struct SigTerm<T: Send> {
-    error: OnceCell<T>,
+// directory the files crawled from `dir` are named relative to by default
+fn default_base(dir: &Path) -> PathBuf {
+    match dir.is_file() {
+        true => dir.parent().unwrap_or(Path::new("")).into(),
+        false => dir.into(),
+    }
+}
+
+/// `path` relative to `base` with `/` separators and without `.` components, so the same
+/// file gets the same name however the crawled path was spelled
+pub(crate) fn relative(base: &Path, path: &Path) -> String {
+    let path = path.strip_prefix(base).unwrap_or(path);
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `paths` without the ones another of them already covers, in their original order.
+///
+/// Paths are compared canonically, so `src`, `./src/` and `src/app.py` crawl the files of
+/// `src` once. Paths that can't be canonicalized, e.g. missing ones, are kept as is.
+pub fn roots<P: AsRef<Path>>(paths: &[P]) -> Vec<PathBuf> {
+    let paths = paths
+        .iter()
+        .map(|p| {
+            let p = p.as_ref();
+            (p, p.canonicalize().unwrap_or_else(|_| p.to_path_buf()))
+        })
+        .collect::<Vec<_>>();
+
+    // a duplicate is covered by its first occurrence
+    let covered = |i: usize, path: &Path| {
+        paths
+            .iter()
+            .enumerate()
+            .any(|(j, (_, other))| j != i && path.starts_with(other) && (path != other || j < i))
+    };
+    paths
+        .iter()
+        .enumerate()
+        .filter(|(i, (_, canonical))| !covered(*i, canonical))
+        .map(|(_, (path, _))| path.to_path_buf())
+        .collect()
 }
 
-impl<T: Send> SigTerm<T> {
-    pub fn new() -> Self {
+/// Prefixes telling apart the names of files crawled from each of `paths`, e.g. the same
+/// `app.py` under two of them.
+///
+/// Each prefix is the base of its path relative to the directory common to all of them,
+/// compared canonically. A single path gets none, so names don't depend on where it lives.
+pub(crate) fn prefixes<P: AsRef<Path>>(paths: &[P]) -> Vec<String> {
+    if paths.len() < 2 {
+        return vec![String::new(); paths.len()];
+    }
+
+    let bases = paths
+        .iter()
+        .map(|p| {
+            let base = default_base(p.as_ref());
+            let dir = match base.as_os_str().is_empty() {
+                true => Path::new("."),
+                false => &base,
+            };
+            dir.canonicalize().unwrap_or(base)
+        })
+        .collect::<Vec<_>>();
+    let common = bases[1..].iter().fold(bases[0].clone(), |common, base| {
+        common
+            .components()
+            .zip(base.components())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    });
+    bases.iter().map(|base| relative(&common, base)).collect()
+}
+
+/// Matcher for gitignore-style `globs` relative to `root`
+pub(crate) fn globs(root: &Path, globs: &[String]) -> Result<Gitignore, ignore::Error> {
+    let mut builder = GitignoreBuilder::new(root);
+    for glob in globs {
+        builder.add_line(None, glob)?;
+    }
+    builder.build()
+}
+
+// files a crawl visits, on top of the standard filters
+struct Filter {
+    root: PathBuf,
+    exts: Vec<String>,
+    include: Gitignore,
+    exclude: Gitignore,
+}
+
+impl Filter {
+    fn new(opts: &CrawlOpts) -> Self {
+        let build = |list| globs(&opts.dir, list).expect("globs are checked when added");
         Self {
-            error: OnceCell::new(),
+            root: opts.dir.clone(),
+            exts: opts.allowed_exts.clone(),
+            include: build(&opts.include),
+            exclude: build(&opts.exclude),
         }
     }
 
-    pub fn get(&self) -> Option<&T> {
-        self.error.get()
-    }
+    fn allows(&self, path: &Path) -> bool {
+        let allowed_ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| self.exts.iter().any(|a| a == ext));
 
-    pub fn set(&self, msg: T) {
-        let _ = self.error.set(msg);
+        // like ignore files, globs don't apply to the path being crawled itself
+        let matches =
+            |globs: &Gitignore| globs.matched_path_or_any_parents(path, false).is_ignore();
+        let selected = path == self.root
+            || (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude);
+
+        allowed_ext && selected
     }
 }
 
 pub(crate) struct Crawler {
     opts: Arc<CrawlOpts>,
+    filter: Arc<Filter>,
+    base: PathBuf,
 }
 
 impl Crawler {
     pub fn new(opts: CrawlOpts) -> Self {
+        let base = match &opts.base {
+            Some(base) => base.clone(),
+            None => default_base(&opts.dir),
+        };
         Self {
+            filter: Arc::new(Filter::new(&opts)),
             opts: Arc::new(opts),
+            base,
         }
     }
 
-    pub fn crawl<F, V, I>(&self, f: F, v: V) -> crate::Result<()>
+    /// Directory the crawled files are named relative to, see [`CrawlOpts::base`]
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Prefix of the names of crawled files, see [`CrawlOpts::prefix`]
+    pub fn prefix(&self) -> &str {
+        &self.opts.prefix
+    }
+
+    /// Name of a crawled file relative to the crawl's base, which node ids derive from
+    pub fn relative(&self, path: &Path) -> String {
+        let name = relative(&self.base, path);
+        match self.opts.prefix.is_empty() {
+            true => name,
+            false => format!("{}/{name}", self.opts.prefix),
+        }
+    }
+
+    fn walker(&self, dir: &Path) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(dir);
+        builder
+            .follow_links(false)
+            .standard_filters(true)
+            .threads(self.opts.threads);
+        builder
+    }
+
+    /// Files at or under `target` the crawl would visit, applying the same filters.
+    ///
+    /// Only the directories leading to `target` are listed, so this stays cheap for a
+    /// single file deep in a large tree.
+    pub fn accepted(&self, target: &Path) -> Vec<PathBuf> {
+        if !target.starts_with(&self.opts.dir) {
+            return vec![];
+        }
+
+        let target = target.to_path_buf();
+        let filter = target.clone();
+        self.walker(&self.opts.dir)
+            .filter_entry(move |e| filter.starts_with(e.path()) || e.path().starts_with(&filter))
+            .build()
+            .flatten()
+            .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+            .filter(|e| e.path().starts_with(&target) && self.filter.allows(e.path()))
+            .map(|e| e.into_path())
+            .collect()
+    }
+
+    /// Visits every allowed file, returning the failures of files that could not be processed
+    /// then those of the entries the walker could not read, e.g. directories.
+    ///
+    /// In fail-fast mode the walk stops at the first failure, which is returned as the error.
+    /// `f` failing with [`Error::Cancelled`] stops the walk in any mode. Unreadable entries
+    /// never stop it, unless failing fast on a crawled path that can't be read itself, e.g. a
+    /// missing one.
+    pub fn crawl<F, V, I>(&self, f: F, v: V) -> crate::Result<(Vec<Diagnostic>, Vec<Diagnostic>)>
     where
         F: Fn(&DirEntry) -> crate::Result<I> + Send + Sync,
         V: Visitor<Item = I>,
     {
         let opts = Arc::clone(&self.opts);
+        let filter = Arc::clone(&self.filter);
         let f = Arc::new(f);
         let visitor = Arc::new(v);
 
-        let sigterm = SigTerm::new();
+        let diagnostics = Arc::new(Mutex::new(vec![]));
+        let unreadable = Arc::new(Mutex::new(vec![]));
+        let cancelled = Arc::new(AtomicBool::new(false));
 
-        WalkBuilder::new(&self.opts.dir)
-            .follow_links(false)
-            .standard_filters(true)
-            .threads(opts.threads)
-            .build_parallel()
-            .run(|| {
-                let opts = Arc::clone(&opts);
-                let f = Arc::clone(&f);
-                let visitor = Arc::clone(&visitor);
-                let sigterm = sigterm.clone();
-
-                Box::new(move |result| {
-                    let Ok(entry) = result else {
-                        return WalkState::Continue;
-                    };
-
-                    let is_allowed = entry
-                        .path()
-                        .extension()
-                        .and_then(|e| e.to_str())
-                        .is_some_and(|ext| opts.allowed_exts.iter().any(|a| a == ext));
-
-                    if is_allowed {
-                        match f(&entry) {
-                            Ok(res) => visitor.visit(res),
-                            Err(e) => sigterm.set(e.to_string()),
-                        };
+        self.walker(&self.opts.dir).build_parallel().run(|| {
+            let opts = Arc::clone(&opts);
+            let filter = Arc::clone(&filter);
+            let f = Arc::clone(&f);
+            let visitor = Arc::clone(&visitor);
+            let diagnostics = Arc::clone(&diagnostics);
+            let unreadable = Arc::clone(&unreadable);
+            let cancelled = Arc::clone(&cancelled);
+
+            Box::new(move |result| {
+                let diagnostic = match result {
+                    Ok(entry) if filter.allows(entry.path()) => match f(&entry) {
+                        Ok(res) => {
+                            visitor.visit(res);
+                            return WalkState::Continue;
+                        }
+                        Err(Error::Cancelled) => {
+                            cancelled.store(true, Ordering::Relaxed);
+                            return WalkState::Quit;
+                        }
+                        Err(e) => Diagnostic::new(entry.path(), e),
+                    },
+                    Ok(_) => return WalkState::Continue,
+                    Err(_) if cancelled.load(Ordering::Relaxed) => return WalkState::Quit,
+                    Err(e) => {
+                        let path = error_path(&e).unwrap_or(&opts.dir).to_path_buf();
+                        let diagnostic = Diagnostic::new(path, without_path(e));
+                        // nothing at all gets crawled from a root that can't be read
+                        if !opts.fail_fast || diagnostic.path != opts.dir {
+                            unreadable.lock().unwrap().push(diagnostic);
+                            return WalkState::Continue;
+                        }
+                        diagnostic
                     }
-                    WalkState::Continue
-                })
-            });
+                };
+
+                diagnostics.lock().unwrap().push(diagnostic);
+                match opts.fail_fast {
+                    true => WalkState::Quit,
+                    false => WalkState::Continue,
+                }
+            })
+        });
+
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+
+        let mut diagnostics = std::mem::take(&mut *diagnostics.lock().unwrap());
+        if self.opts.fail_fast && !diagnostics.is_empty() {
+            return Err(diagnostics.swap_remove(0).into());
+        }
+        let unreadable = std::mem::take(&mut *unreadable.lock().unwrap());
+        Ok((diagnostics, unreadable))
+    }
+}
+
+// the diagnostic holding the error already names its path
+fn without_path(e: ignore::Error) -> ignore::Error {
+    match e {
+        ignore::Error::WithPath { err, .. } => without_path(*err),
+        ignore::Error::WithDepth { depth, err } => ignore::Error::WithDepth {
+            depth,
+            err: Box::new(without_path(*err)),
+        },
+        e => e,
+    }
+}
 
-        // check if error occured during build
-        if let Some(msg) = sigterm.get() {
-            return Err(Error::other(msg));
+fn error_path(e: &ignore::Error) -> Option<&Path> {
+    match e {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
         }
-        Ok(())
+        _ => None,
     }
 }
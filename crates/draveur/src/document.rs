@@ -0,0 +1,151 @@
+//! On-disk graph format, so saved analyses can be read back for diffing, caching or merging.
+//!
+//! A document is a single json object holding the format version, how the graphs were
+//! produced and the graphs themselves:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "draveur": "0.1.0",
+//!   "roots": ["src"],
+//!   "rules": ["functions", "classes", "rules/tasks.scm"],
+//!   "elapsed_ms": 42,
+//!   "files": 2,
+//!   "diagnostics": [{"path": "src/broken.py", "message": "failed to parse tree"}],
+//!   "graphs": [
+//!     [
+//!       {"id": 12, "edges": [{"sink": 34, "attrs": {"kind": "call"}}], "attrs": {"name": "run"}},
+//!       {"id": 34, "edges": [], "attrs": {"name": "fetch", "type": "call"}}
+//!     ]
+//!   ]
+//! }
+//! ```
+//!
+//! - every field but `version` and `graphs` describes the run and may be left out
+//! - `files` counts the crawled files, including the ones that failed, while `diagnostics`
+//!   also lists the entries that could not be read, e.g. directories
+//! - each graph is an array of nodes, its root first
+//! - node ids are unique across the document and below 2^53
+//! - edge sinks are ids of nodes in the document, usually of the same graph, though calls
+//!   linked by [`resolve_calls`](crate::resolve::resolve_calls) point into other graphs
+//! - attribute values are `null`, booleans, integers below 2^32, strings or arrays of values,
+//!   values tagged with their type as earlier versions wrote them, e.g.
+//!   `{"type": "int", "int": 3}`, are read too
+//!
+//! The version changes whenever a document written by a newer draveur could be misread by an
+//! older one, which then rejects it instead.
+
+use roaring::RoaringTreemap;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+use crate::types::Graph;
+use crate::{Diagnostic, Error, Result, report};
+
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Version of the draveur crate, recorded in the documents it writes
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Serialize, Deserialize)]
+pub struct Document<'a> {
+    pub version: u32,
+    #[serde(default)]
+    pub draveur: String,
+    /// Crawled paths
+    #[serde(default)]
+    pub roots: Vec<String>,
+    /// Identifiers of the rules the graphs come from, e.g. rule set names
+    #[serde(default)]
+    pub rules: Vec<String>,
+    #[serde(default)]
+    pub elapsed_ms: u64,
+    #[serde(default)]
+    pub files: usize,
+    #[serde(default)]
+    pub diagnostics: Vec<Report>,
+    pub graphs: Cow<'a, [Graph]>,
+}
+
+/// File that could not be processed, and why
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Report {
+    pub path: String,
+    pub message: String,
+}
+
+impl From<&Diagnostic> for Report {
+    fn from(diagnostic: &Diagnostic) -> Self {
+        Self {
+            path: diagnostic.path.display().to_string(),
+            message: report(&diagnostic.error),
+        }
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Document of `graphs` written by this version of draveur, without run details
+    pub fn new(graphs: &'a [Graph]) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            draveur: VERSION.to_string(),
+            roots: vec![],
+            rules: vec![],
+            elapsed_ms: 0,
+            files: 0,
+            diagnostics: vec![],
+            graphs: Cow::Borrowed(graphs),
+        }
+    }
+
+    pub fn write(&self, w: impl Write) -> Result<()> {
+        serde_json::to_writer(w, self)?;
+        Ok(())
+    }
+
+    pub fn write_pretty(&self, w: impl Write) -> Result<()> {
+        serde_json::to_writer_pretty(w, self)?;
+        Ok(())
+    }
+
+    pub fn into_graphs(self) -> Vec<Graph> {
+        self.graphs.into_owned()
+    }
+}
+
+impl Document<'static> {
+    /// Reads a document written by this version of the format, checking that node ids are
+    /// unique and that every edge targets a node of the document
+    pub fn read(r: impl Read) -> Result<Self> {
+        // the version alone is read first, later versions may not parse as this one
+        let value = serde_json::from_reader::<_, serde_json::Value>(r)?;
+        let version = value.get("version").and_then(serde_json::Value::as_u64);
+        if version != Some(FORMAT_VERSION as u64) {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        let document = serde_json::from_value::<Self>(value)?;
+        document.validate()?;
+        Ok(document)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut ids = RoaringTreemap::new();
+        for node in self.graphs.iter().flat_map(Graph::iter) {
+            if !ids.insert(node.id()) {
+                return Err(Error::DuplicateNode(node.id()));
+            }
+        }
+
+        for node in self.graphs.iter().flat_map(Graph::iter) {
+            if let Some(edge) = node.edges().iter().find(|e| !ids.contains(e.sink())) {
+                return Err(Error::DanglingEdge {
+                    node: node.id(),
+                    sink: edge.sink(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
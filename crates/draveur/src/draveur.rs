@@ -1,27 +1,42 @@
 use crate::{
-    IoErrorKind, Result,
-    crawl::{CrawlOpts, Visitor},
-    errors::Error,
+    Diagnostic, IoErrorKind, Result, TreeSitterError,
+    cache::Cache,
+    cancel::{Budget, CancellationToken},
+    config::Config,
+    crawl::{self, CrawlOpts, Crawler, Visitor},
+    errors::{Error, ExecutionFailure},
+    hash,
     lang::Lang,
-    types::Graph,
     parse::Noeud,
+    rules,
+    types::{Graph, LOCATION_ATTR, MATCH_ATTR, VARIABLE_ATTR},
 };
-use crossbeam_channel::{Sender, unbounded};
+use crossbeam_channel::{Sender, bounded};
 use ignore::DirEntry;
 use madvise::{AccessPattern, AdviseMemory};
 use memmap2::{Mmap, MmapOptions};
 use std::env;
-use std::thread::available_parallelism;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread::{self, available_parallelism};
+use std::time::Duration;
 use std::{cell::UnsafeCell, marker::PhantomData};
-use std::{fs::File, io::Read, path::Path};
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 use thread_local::ThreadLocal;
-use tree_sitter::{Parser, Query};
+use tree_sitter::{Node, Parser, Point, Query, Range, Tree};
 use tree_sitter_graph::{
-    ExecutionConfig, Identifier, NoCancellation, Variables, ast, functions::Functions, graph::Value,
+    CancellationFlag, ExecutionConfig, ExecutionError, Identifier, Variables, ast,
+    functions::Functions, graph::Value,
 };
 
 static MMAP_MIN_SIZE: usize = 8192;
 
+// files parsed ahead of the consumer, per crawler thread
+const QUEUE_PER_THREAD: usize = 4;
+
 fn available_threads() -> usize {
     env::var("THREADS")
         .ok()
@@ -67,27 +82,78 @@ fn buffered(path: &Path, file_size: usize) -> Result<FileBuffer> {
 
 #[derive(Debug, Clone)]
 struct State {
-    // pushes subgraphs from thread to an mpsc queue
-    tx: Sender<serde_json::Value>,
+    // pushes each file's subgraphs from thread to an mpsc queue
+    tx: Sender<(PathBuf, Vec<Graph>)>,
 }
 
 impl Visitor for State {
-    type Item = Vec<Option<serde_json::Value>>;
+    type Item = (PathBuf, Vec<Graph>);
 
-    fn visit(&self, value: Self::Item) {
-        for v in value.into_iter().flatten() {
-            self.tx.send(v).expect("failed to send");
-        }
+    fn visit(&self, (path, graphs): Self::Item) {
+        // the consumer drains the queue until the crawl ends, or drops it once it panicked
+        let _ = self.tx.send((path, graphs));
     }
 }
 
+// subgraphs of each crawled file
+pub(crate) type Files = Vec<(PathBuf, Vec<Graph>)>;
+
+/// Outcome of a crawl, see [`Draveur::crawl`]
+pub(crate) struct Crawled {
+    pub files: Files,
+    /// Files that could not be processed
+    pub failed: Vec<Diagnostic>,
+    /// Entries the walker could not read, e.g. directories
+    pub unreadable: Vec<Diagnostic>,
+}
+
+/// Graphs of a crawl along with the files that could not be processed
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub graphs: Vec<Graph>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Number of crawled files, including the ones that failed but not the entries that
+    /// could not be read
+    pub files: usize,
+}
+
 pub struct Draveur<L: Lang> {
-    mappings: Vec<(Query, ast::File)>,
+    // queries and their stanzas, along with the name of the rule if any
+    mappings: Vec<(Option<String>, Query, ast::File)>,
+
+    // number of crawler threads, 0 defers to `available_threads`
+    threads: usize,
+
+    // hash of every rule added so far, invalidates cached files when rules change
+    rules: u64,
+
+    cache: Option<Cache>,
+
+    // abort on the first failing file, otherwise skip it and report it as a diagnostic
+    fail_fast: bool,
+
+    cancel: CancellationToken,
+
+    // time each file may take before being reported as failed
+    timeout: Option<Duration>,
+
+    // gitignore-style globs relative to each crawled path
+    include: Vec<String>,
+    exclude: Vec<String>,
 
     // marker type for provided language
     _phantom: PhantomData<L>,
 }
 
+impl<L> Default for Draveur<L>
+where
+    L: Lang + Sync,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<L> Draveur<L>
 where
     L: Lang + Sync,
@@ -95,126 +161,599 @@ where
     pub fn new() -> Self {
         Self {
             mappings: Vec::new(),
+            threads: 0,
+            rules: hash::chain(
+                hash::hash(env!("CARGO_PKG_VERSION").as_bytes()),
+                L::NAME.as_bytes(),
+            ),
+            cache: None,
+            fail_fast: true,
+            cancel: CancellationToken::new(),
+            timeout: None,
+            include: vec![],
+            exclude: vec![],
             _phantom: PhantomData,
         }
     }
 
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Whether to abort on the first file failing to process (the default) or to keep going
+    pub fn fail_fast(&mut self, fail_fast: bool) -> &mut Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Stops crawls once `token` is cancelled, e.g. from another thread, failing them with
+    /// [`Error::Cancelled`]
+    pub fn cancellation(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Token stopping the crawls of this draveur, see [`Draveur::cancellation`]
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Time budget of each file, files taking longer fail with [`Error::Timeout`]
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Reuses the subgraphs of unchanged files across runs, stored under `dir`
+    pub fn cache(&mut self, dir: impl Into<PathBuf>) -> Result<&mut Self> {
+        self.cache = Some(Cache::new(dir)?);
+        Ok(self)
+    }
+
+    /// Only crawls files matching one of the included globs, relative to the crawled path
+    pub fn include(&mut self, glob: impl Into<String>) -> Result<&mut Self> {
+        let glob = glob.into();
+        crawl::globs(Path::new(""), std::slice::from_ref(&glob))?;
+        self.include.push(glob);
+        Ok(self)
+    }
+
+    /// Skips files matching the glob, relative to the crawled path
+    pub fn exclude(&mut self, glob: impl Into<String>) -> Result<&mut Self> {
+        let glob = glob.into();
+        crawl::globs(Path::new(""), std::slice::from_ref(&glob))?;
+        self.exclude.push(glob);
+        Ok(self)
+    }
+
+    /// Applies the crawl settings of `config`, its rule sets are up to the language
+    pub fn configure(&mut self, config: &Config) -> Result<&mut Self> {
+        if let Some(threads) = config.threads {
+            self.threads(threads);
+        }
+        for glob in &config.include {
+            self.include(glob)?;
+        }
+        for glob in &config.exclude {
+            self.exclude(glob)?;
+        }
+        Ok(self)
+    }
+
+    /// Adds a rule from a query file and a stanza file, expanding their includes. The rule is
+    /// named after the stanza file
+    pub fn load(
+        &mut self,
+        query: impl AsRef<Path>,
+        stanzas: impl AsRef<Path>,
+    ) -> Result<&mut Self> {
+        let stanzas = stanzas.as_ref();
+        self.push(
+            Some(stanzas.display().to_string()),
+            rules::read::<L>(query)?,
+            rules::read::<L>(stanzas)?,
+        )
+    }
+
     pub fn add(&mut self, cause: String, effect: String) -> Result<&mut Self> {
+        self.push(None, cause, effect)
+    }
+
+    /// Like [`Draveur::add`], naming the rule in its failures, e.g. after its rule set
+    pub fn add_named(
+        &mut self,
+        name: impl Into<String>,
+        cause: String,
+        effect: String,
+    ) -> Result<&mut Self> {
+        self.push(Some(name.into()), cause, effect)
+    }
+
+    fn push(&mut self, name: Option<String>, cause: String, effect: String) -> Result<&mut Self> {
+        self.rules = hash::chain(hash::chain(self.rules, cause.as_bytes()), effect.as_bytes());
         self.mappings
-            .push((L::build_query(cause)?, L::build_stanzas(effect)?));
+            .push((name, L::build_query(cause)?, L::build_stanzas(effect)?));
         Ok(self)
     }
 
+    /// Graphs of every file under `path`, ordered by file path, source position then rule.
+    ///
+    /// Files failing to process are skipped unless failing fast.
     pub fn waltz(&self, path: &str) -> Result<Vec<Graph>> {
-        let tls = ThreadLocal::with_capacity(available_threads());
+        Ok(self.analyze(path)?.graphs)
+    }
 
-        // FIXME: add backpressure later
-        let (tx, rx) = unbounded();
-        let state = State { tx };
+    /// Like [`Draveur::waltz`], also returning the failures of the skipped files
+    pub fn analyze(&self, path: &str) -> Result<Analysis> {
+        self.analyze_paths(&[path])
+    }
 
-        let crawler = CrawlOpts::default()
+    /// Like [`Draveur::analyze`] over each of `paths` in turn.
+    ///
+    /// Files are named relative to a directory common to all the paths, so node ids stay
+    /// unique across them, e.g. for the same `app.py` under two paths.
+    pub fn analyze_paths<P: AsRef<Path>>(&self, paths: &[P]) -> Result<Analysis> {
+        let mut analysis = Analysis::default();
+        for crawler in self.crawlers(paths) {
+            let crawled = self.crawl(&crawler)?;
+            analysis.files += crawled.files.len() + crawled.failed.len();
+            analysis
+                .graphs
+                .extend(crawled.files.into_iter().flat_map(|(_, graphs)| graphs));
+            analysis.diagnostics.extend(crawled.failed);
+            analysis.diagnostics.extend(crawled.unreadable);
+        }
+        Ok(analysis)
+    }
+
+    /// Crawlers of `paths`, naming files apart across them, see [`Draveur::analyze_paths`]
+    pub(crate) fn crawlers<P: AsRef<Path>>(&self, paths: &[P]) -> Vec<Crawler> {
+        paths
+            .iter()
+            .zip(crawl::prefixes(paths))
+            .map(|(path, prefix)| self.crawl_opts(path.as_ref()).prefix(prefix).build())
+            .collect()
+    }
+
+    pub(crate) fn crawl_opts(&self, path: impl Into<PathBuf>) -> CrawlOpts {
+        CrawlOpts::default()
             .path(path)
-            .threads(available_threads())
+            .threads(self.thread_count())
+            .fail_fast(self.fail_fast)
+            .globs(&self.include, &self.exclude)
             .add_lang::<L>()
-            .build();
+    }
 
-        crawler.crawl(|e| self.parse_file(e, &tls), state)?;
+    fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => available_threads(),
+            n => n,
+        }
+    }
 
-        let graphs = rx
-            .iter()
-            .map(|item| Graph::deser(item))
-            .flatten()
-            .collect::<Vec<_>>();
+    /// Subgraphs of every file visited by `crawler` grouped by file, and what failed
+    pub(crate) fn crawl(&self, crawler: &Crawler) -> Result<Crawled> {
+        let mut files = vec![];
+        let (failed, unreadable) =
+            self.stream_with(crawler, |path, graphs| files.push((path, graphs)))?;
 
-        // merge(&mut graphs, |leaf, root| leaf.get("src") == root.get("src"));
+        // workers finish in any order
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-        Ok(graphs)
+        Ok(Crawled {
+            files,
+            failed,
+            unreadable,
+        })
+    }
+
+    /// Calls `f` with the subgraphs of each file under `path` as soon as they are produced.
+    ///
+    /// Files come in the order workers finish them. Workers wait while the queue of files
+    /// `f` has yet to consume is full, so memory stays bounded however large the crawl.
+    /// Returns the files that failed, unless failing fast. Once cancelled, `f` isn't called
+    /// anymore.
+    pub fn stream<F>(&self, path: &str, f: F) -> Result<Vec<Diagnostic>>
+    where
+        F: FnMut(PathBuf, Vec<Graph>),
+    {
+        self.stream_paths(&[path], f)
     }
 
+    /// Like [`Draveur::stream`] over each of `paths` in turn, naming files as
+    /// [`Draveur::analyze_paths`] does
+    pub fn stream_paths<P, F>(&self, paths: &[P], mut f: F) -> Result<Vec<Diagnostic>>
+    where
+        P: AsRef<Path>,
+        F: FnMut(PathBuf, Vec<Graph>),
+    {
+        let mut diagnostics = vec![];
+        for crawler in self.crawlers(paths) {
+            let (failed, unreadable) = self.stream_with(&crawler, &mut f)?;
+            diagnostics.extend(failed);
+            diagnostics.extend(unreadable);
+        }
+        Ok(diagnostics)
+    }
+
+    /// Failures of the files `crawler` visits, then of the entries it could not read
+    fn stream_with<F>(
+        &self,
+        crawler: &Crawler,
+        mut f: F,
+    ) -> Result<(Vec<Diagnostic>, Vec<Diagnostic>)>
+    where
+        F: FnMut(PathBuf, Vec<Graph>),
+    {
+        let tls = ThreadLocal::with_capacity(self.thread_count());
+        let (tx, rx) = bounded(self.thread_count() * QUEUE_PER_THREAD);
+        let state = State { tx };
+
+        thread::scope(|s| {
+            let crawl = s.spawn(|| {
+                crawler.crawl(
+                    |e| {
+                        let name = crawler.relative(e.path());
+                        Ok((e.path().to_path_buf(), self.parse_file(e, &name, &tls)?))
+                    },
+                    state,
+                )
+            });
+
+            let drained = panic::catch_unwind(AssertUnwindSafe(|| {
+                // files parsed before a cancellation are left undelivered
+                for (path, graphs) in rx.iter().take_while(|_| !self.is_cancelled()) {
+                    f(path, graphs);
+                }
+            }));
+
+            // otherwise workers wait on a full queue nobody drains anymore
+            drop(rx);
+            let crawled = crawl.join().expect("crawler panicked");
+            if let Err(panic) = drained {
+                panic::resume_unwind(panic);
+            }
+            // fails even once the crawl was over, some of its files may have gone undelivered
+            match self.is_cancelled() {
+                true => Err(Error::Cancelled),
+                false => crawled,
+            }
+        })
+    }
+
+    /// Subgraphs of a crawled file, `name` being its path relative to the crawl's base
     fn parse_file(
         &self,
         entry: &DirEntry,
+        name: &str,
         tls: &ThreadLocal<UnsafeCell<Parser>>,
-    ) -> Result<Vec<Option<serde_json::Value>>> {
+    ) -> Result<Vec<Graph>> {
+        if self.cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let budget = Budget::new(&self.cancel, self.timeout);
+
         let file_size = entry.metadata()?.len() as usize;
         let buf = buffered(entry.path(), file_size)?;
         let bytes = buf.bytes();
 
-        let parser = tls.get_or_try(|| {
-            let mut p = Parser::new();
-            p.set_language(&L::language())
-                .map_err(|e| Error::lang::<L>(e))?;
+        let content = hash::hash(bytes);
+        // node ids derive from the name, so entries only hold for the same one
+        let rules = hash::chain(self.rules, name.as_bytes());
+        if let Some(cache) = &self.cache
+            && let Some(graphs) = cache.get(entry.path(), content, rules)
+        {
+            return Ok(graphs);
+        }
 
-            Ok::<UnsafeCell<Parser>, Error>(UnsafeCell::new(p))
-        })?;
+        let parser = tls.get_or_try(|| Ok::<_, Error>(UnsafeCell::new(Self::parser()?)))?;
 
         // SAFETY: we're the only one accessing this parser
         let parser = unsafe { &mut *parser.get() };
-        let tree = parser.parse(bytes, None).ok_or_else(|| Error::Parse)?;
+        let graphs = self.build(parser, entry.path(), name, bytes, &budget)?;
+
+        // an entry that can't be written only costs the next run a parse
+        if let Some(cache) = &self.cache {
+            let _ = cache.put(entry.path(), content, rules, &graphs);
+        }
+
+        Ok(graphs)
+    }
+
+    /// Graphs of `source` as if read from `path`, without crawling nor caching
+    pub fn analyze_source(&self, path: impl AsRef<Path>, source: &[u8]) -> Result<Vec<Graph>> {
+        let path = path.as_ref();
+        let name = crawl::relative(Path::new(""), path);
+        let budget = Budget::new(&self.cancel, self.timeout);
+        self.build(&mut Self::parser()?, path, &name, source, &budget)
+    }
+
+    fn parser() -> Result<Parser> {
+        let mut p = Parser::new();
+        p.set_language(&L::language())
+            .map_err(|e| Error::lang::<L>(e))?;
+        Ok(p)
+    }
+
+    /// Subgraphs of every rule match in a file, in source order then rule order.
+    ///
+    /// Node ids derive from `name` rather than `path`, so they don't depend on how the
+    /// crawled path was spelled nor where it lives.
+    fn build(
+        &self,
+        parser: &mut Parser,
+        path: &Path,
+        name: &str,
+        bytes: &[u8],
+        budget: &Budget,
+    ) -> Result<Vec<Graph>> {
+        // e.g. latin-1 files under a PEP 263 coding line, parsed as decoded so byte offsets
+        // into the tree stay valid in `source`
+        let source = String::from_utf8_lossy(bytes);
+        let bytes = source.as_bytes();
+        let tree = self.parse(parser, bytes, None, path, budget)?;
 
         let root = Noeud::new(tree.root_node(), bytes);
         let mut graphs = vec![];
 
-        // iterate over all the capture groups
-        for (cause, effect) in &self.mappings {
-            for (_group, noeud) in root
-                .parse(cause)
+        for (rule, (_, cause, effect)) in self.mappings.iter().enumerate() {
+            let matches = root
+                .parse_within(cause, budget.timeout_micros())
                 .flatten()
                 .filter(|(_, node)| !node.is_empty())
-            {
-                graphs.push(Self::build_node_graph(&noeud, effect, entry, tls)?);
+                .map(|(_group, noeud)| noeud)
+                .collect::<Vec<_>>();
+
+            // matches stop early rather than fail once out of time
+            budget
+                .check("matching")
+                .map_err(|_| self.interrupted(path))?;
+            if matches.is_empty() {
+                continue;
+            }
+
+            let subgraphs = self.build_rule_graphs(
+                parser, &tree, &source, &matches, rule, effect, path, name, budget,
+            )?;
+            for (noeud, graph) in matches.iter().zip(subgraphs) {
+                graphs.push((noeud.node.start_byte(), rule, graph));
             }
         }
 
-        Ok(graphs)
+        // in source order, then rule order
+        graphs.sort_by_key(|&(start, rule, _)| (start, rule));
+        Ok(graphs
+            .into_iter()
+            .filter_map(|(_, _, graph)| graph)
+            .collect())
+    }
+
+    /// Parses within the file's budget, `None` from the parser means it ran out of time.
+    ///
+    /// Reparsing with the file's own tree as `old` reuses its unchanged subtrees.
+    fn parse(
+        &self,
+        parser: &mut Parser,
+        bytes: &[u8],
+        old: Option<&Tree>,
+        path: &Path,
+        budget: &Budget,
+    ) -> Result<Tree> {
+        parser.set_timeout_micros(budget.timeout_micros());
+
+        let tree = parser.parse(bytes, old);
+        if tree.is_none() {
+            // otherwise the next file parsed on this thread resumes the unfinished parse
+            parser.reset();
+        }
+        match tree {
+            Some(tree) => Ok(tree),
+            None if budget.is_exhausted() => Err(self.interrupted(path)),
+            None => Err(Error::Parse),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
     }
 
-    fn build_node_graph(
-        node: &Noeud,
+    /// Error for a file whose processing was stopped
+    fn interrupted(&self, path: &Path) -> Error {
+        match (self.cancel.is_cancelled(), self.timeout) {
+            (false, Some(budget)) => Error::Timeout {
+                file: path.display().to_string(),
+                budget,
+            },
+            _ => Error::Cancelled,
+        }
+    }
+
+    /// Runs the stanzas of `rule` once over all its matches, then splits the result into one
+    /// subgraph per match, `None` for the matches the stanzas create no node in.
+    ///
+    /// The stanzas see a tree of the matched ranges only, so code the query didn't select can
+    /// neither add nodes nor fail the file, while positions stay those of the whole file.
+    #[allow(clippy::too_many_arguments)]
+    fn build_rule_graphs(
+        &self,
+        parser: &mut Parser,
+        tree: &Tree,
+        source: &str,
+        matches: &[Noeud],
+        rule: usize,
         stanzas: &ast::File,
-        entry: &DirEntry,
-        tls: &ThreadLocal<UnsafeCell<Parser>>,
-    ) -> Result<Option<serde_json::Value>> {
+        path: &Path,
+        name: &str,
+        budget: &Budget,
+    ) -> Result<Vec<Option<Graph>>> {
+        let mut ranges = matches
+            .iter()
+            .map(|noeud| noeud.node.range())
+            .collect::<Vec<_>>();
+        let scoped = self.parse_ranges(parser, tree, source, &mut ranges, path, budget)?;
+
+        let file = path.display().to_string();
         let mut globals = Variables::new();
         globals
-            .add(
-                Identifier::from("global_filename"),
-                entry.path().display().to_string().into(),
-            )
-            .unwrap();
-        globals
-            .add(
-                Identifier::from("global_row"),
-                Value::Integer(node.node.start_position().row as u32),
-            )
-            .unwrap();
-        globals
-            .add(
-                Identifier::from("global_column"),
-                Value::Integer(node.node.start_position().column as u32),
-            )
+            .add(Identifier::from("global_filename"), file.clone().into())
             .unwrap();
+        // offsets of the tree within the file, kept for stanzas adding them to positions
+        for offset in ["global_row", "global_column"] {
+            globals
+                .add(Identifier::from(offset), Value::Integer(0))
+                .unwrap();
+        }
 
-        // parse node sub-tree
-        let node_tree = {
-            // SAFETY: must already exist as this function is run after `Self::parse_file`
-            let parser = tls.get().unwrap();
-            let parser = unsafe { &mut *parser.get() };
-            parser
-                .parse(node.bytes(), None)
-                .ok_or_else(|| Error::Parse)?
+        let functions = Functions::stdlib();
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(true)
+            .debug_attributes(
+                Identifier::from(LOCATION_ATTR),
+                Identifier::from(VARIABLE_ATTR),
+                Identifier::from(MATCH_ATTR),
+            );
+
+        let graph = match stanzas.execute(&scoped, source, &config, budget) {
+            Ok(graph) => graph,
+            Err(ExecutionError::Cancelled(_)) => return Err(self.interrupted(path)),
+            Err(error) => {
+                return Err(self.failing_match(
+                    parser, tree, source, matches, rule, stanzas, &config, path, budget, error,
+                ));
+            }
         };
 
-        let functions = Functions::stdlib();
-        let config = ExecutionConfig::new(&functions, &globals).lazy(true);
+        // span of the stanza match that created each node
+        let spans = graph
+            .iter_nodes()
+            .map(|node| {
+                let syntax = graph[node]
+                    .attributes
+                    .get(MATCH_ATTR)?
+                    .as_syntax_node_ref()
+                    .ok()?;
+                Some(graph[syntax].byte_range())
+            })
+            .collect::<Vec<_>>();
 
-        let graph = stanzas
-            .execute(&node_tree, node.ctx_as_str(), &config, &NoCancellation)
-            .unwrap_or_else(|e| panic!("{:?}\n{}", e, node.ctx_as_str()));
+        matches
+            .iter()
+            .map(|noeud| {
+                let range = noeud.node.byte_range();
+                let keep = spans
+                    .iter()
+                    .map(|span| {
+                        span.as_ref()
+                            .is_some_and(|s| range.start <= s.start && s.end <= range.end)
+                    })
+                    .collect::<Vec<_>>();
+                if !keep.contains(&true) {
+                    return Ok(None);
+                }
+
+                let seed = [range.start, range.end, rule]
+                    .iter()
+                    .fold(hash::hash(name.as_bytes()), |h, n| {
+                        hash::chain(h, &n.to_le_bytes())
+                    });
+                Graph::from_tsg(&graph, &keep, seed).map(Some)
+            })
+            .collect()
+    }
 
-        match graph.node_count() {
-            0 => Ok(None),
-            _ => Ok(Some(serde_json::to_value(graph).unwrap())),
+    /// Reparses `source` within `ranges` only, merging the overlapping ones first as
+    /// tree-sitter requires them disjoint and in order.
+    ///
+    /// The parser reads the ranges back to back, so each is extended to the end of its last
+    /// line: otherwise the next range would continue that line, e.g. a python statement.
+    fn parse_ranges(
+        &self,
+        parser: &mut Parser,
+        tree: &Tree,
+        source: &str,
+        ranges: &mut Vec<Range>,
+        path: &Path,
+        budget: &Budget,
+    ) -> Result<Tree> {
+        for range in ranges.iter_mut() {
+            match source[range.end_byte..].find('\n') {
+                Some(newline) => {
+                    range.end_byte += newline + 1;
+                    range.end_point = Point::new(range.end_point.row + 1, 0);
+                }
+                None => {
+                    range.end_point.column += source.len() - range.end_byte;
+                    range.end_byte = source.len();
+                }
+            }
         }
+        ranges.sort_by_key(|range| range.start_byte);
+        ranges.dedup_by(|next, range| {
+            if next.start_byte > range.end_byte {
+                return false;
+            }
+            if next.end_byte > range.end_byte {
+                range.end_byte = next.end_byte;
+                range.end_point = next.end_point;
+            }
+            true
+        });
+
+        parser
+            .set_included_ranges(ranges)
+            .map_err(|_| Error::Parse)?;
+        let scoped = self.parse(parser, source.as_bytes(), Some(tree), path, budget);
+        parser.set_included_ranges(&[]).map_err(|_| Error::Parse)?;
+        scoped
+    }
+
+    /// Failure of the stanzas of `rule`, located by running them over each match alone.
+    ///
+    /// Only runs once the stanzas failed over all the matches, so the common case parses
+    /// once per rule. Failures no single match reproduces span every match.
+    #[allow(clippy::too_many_arguments)]
+    fn failing_match(
+        &self,
+        parser: &mut Parser,
+        tree: &Tree,
+        source: &str,
+        matches: &[Noeud],
+        rule: usize,
+        stanzas: &ast::File,
+        config: &ExecutionConfig,
+        path: &Path,
+        budget: &Budget,
+        error: ExecutionError,
+    ) -> Error {
+        let file = path.display().to_string();
+        let failure = |start: &Node, end: &Node, source_error| {
+            let failure = ExecutionFailure {
+                file: file.clone(),
+                start: start.start_position(),
+                end: end.end_position(),
+                rule,
+                name: self.mappings[rule].0.clone(),
+                snippet: source[start.start_byte()..end.end_byte()].to_string(),
+                source: source_error,
+            };
+            TreeSitterError::Execution(Box::new(failure)).into()
+        };
+
+        for noeud in matches {
+            let mut ranges = vec![noeud.node.range()];
+            let scoped = match self.parse_ranges(parser, tree, source, &mut ranges, path, budget) {
+                Ok(scoped) => scoped,
+                Err(error) => return error,
+            };
+            match stanzas.execute(&scoped, source, config, budget) {
+                Ok(_) => {}
+                Err(ExecutionError::Cancelled(_)) => return self.interrupted(path),
+                Err(error) => return failure(&noeud.node, &noeud.node, error),
+            }
+        }
+
+        let (first, last) = (&matches[0].node, &matches[matches.len() - 1].node);
+        failure(first, last, error)
     }
 }
@@ -1,11 +1,14 @@
 use std::fmt::Debug;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
-use tree_sitter::{LanguageError, QueryError};
-use tree_sitter_graph::ParseError;
+use tree_sitter::{LanguageError, Point, QueryError};
+use tree_sitter_graph::{ExecutionError, ParseError};
 
+use crate::document::FORMAT_VERSION;
 use crate::lang::Lang;
+use crate::types::NodeId;
 
 pub type Result<T> = std::result::Result<T, crate::errors::Error>;
 
@@ -27,14 +30,57 @@ pub enum Error {
     #[error(transparent)]
     Crawl(#[from] ignore::Error),
 
+    #[error(transparent)]
+    Watch(#[from] notify::Error),
+
     #[error("failed to parse tree")]
     Parse,
 
+    #[error("{file}: unknown fragment {name}")]
+    UnknownFragment { file: String, name: String },
+
+    #[error("{file} includes itself")]
+    IncludeCycle { file: String },
+
+    #[error("invalid config {file}")]
+    Config {
+        file: String,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("node id {0} is used by more than one node")]
+    DuplicateNode(NodeId),
+
+    #[error("edge from node {node} to unknown node {sink}")]
+    DanglingEdge { node: NodeId, sink: NodeId },
+
+    #[error("edges form a cycle reaching node {0}")]
+    Cycle(NodeId),
+
+    #[error("unsupported document version {}, expected {FORMAT_VERSION}", version_name(.0))]
+    UnsupportedVersion(Option<u64>),
+
+    #[error("cancelled")]
+    Cancelled,
+
+    #[error("{file} took longer than its {budget:?} budget")]
+    Timeout { file: String, budget: Duration },
+
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
 
     #[error(transparent)]
     Other(#[from] anyhow::Error),
+
+    #[error(transparent)]
+    File(Box<Diagnostic>),
+}
+
+impl From<Diagnostic> for Error {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self::File(Box::new(diagnostic))
+    }
 }
 
 impl Error {
@@ -50,6 +96,46 @@ impl Error {
     }
 }
 
+/// Error message including its sources
+pub fn report(e: &dyn std::error::Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        msg.push_str(&format!(": {e}"));
+        source = e.source();
+    }
+    msg
+}
+
+fn version_name(version: &Option<u64>) -> String {
+    version.map_or("none".into(), |version| version.to_string())
+}
+
+fn rule_name(rule: usize, name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("{rule} ({name})"),
+        None => rule.to_string(),
+    }
+}
+
+/// Failure to process a single file, the error is its source
+#[derive(Error, Debug)]
+#[error("{}", path.display())]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    #[source]
+    pub error: Error,
+}
+
+impl Diagnostic {
+    pub fn new(path: impl Into<PathBuf>, error: impl Into<Error>) -> Self {
+        Self {
+            path: path.into(),
+            error: error.into(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum IoErrorKind {
@@ -59,6 +145,12 @@ pub enum IoErrorKind {
         #[source]
         source: io::Error,
     },
+    #[error("failed to create {file}")]
+    Create {
+        file: String,
+        #[source]
+        source: io::Error,
+    },
     #[error("failed to read {file}")]
     Read {
         file: String,
@@ -71,6 +163,12 @@ pub enum IoErrorKind {
         #[source]
         source: io::Error,
     },
+    #[error("failed to write {file}")]
+    Write {
+        file: String,
+        #[source]
+        source: io::Error,
+    },
 }
 
 impl IoErrorKind {
@@ -86,12 +184,24 @@ impl IoErrorKind {
             source,
         }
     }
+    pub fn create(file: impl AsRef<Path>, source: io::Error) -> Self {
+        Self::Create {
+            file: file.as_ref().display().to_string(),
+            source,
+        }
+    }
     pub fn mmap(file: impl AsRef<Path>, source: io::Error) -> Self {
         Self::Mmap {
             file: file.as_ref().display().to_string(),
             source,
         }
     }
+    pub fn write(file: impl AsRef<Path>, source: io::Error) -> Self {
+        Self::Write {
+            file: file.as_ref().display().to_string(),
+            source,
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -100,4 +210,31 @@ pub enum TreeSitterError {
     Query(QueryError, String),
     #[error("invalid stanzas: {0}\n{1}")]
     Stanzas(ParseError, String),
+    #[error(transparent)]
+    Execution(Box<ExecutionFailure>),
+}
+
+/// Stanzas failing to execute on a node matched by their rule's query
+#[derive(Error, Debug)]
+#[error(
+    "failed to execute rule {} on {file}:{}:{}-{}:{}",
+    rule_name(*rule, name),
+    start.row + 1,
+    start.column + 1,
+    end.row + 1,
+    end.column + 1
+)]
+pub struct ExecutionFailure {
+    pub file: String,
+    /// Range of the matched node in the file
+    pub start: Point,
+    pub end: Point,
+    /// Index of the rule in the order rules were added
+    pub rule: usize,
+    /// Name of the rule, e.g. its stanza file or built-in rule set
+    pub name: Option<String>,
+    /// Source of the matched node
+    pub snippet: String,
+    #[source]
+    pub source: ExecutionError,
 }
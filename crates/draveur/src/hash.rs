@@ -0,0 +1,12 @@
+//! Stable hashes of bytes, the same across runs and machines, e.g. for node ids and cache keys.
+
+use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_seed};
+
+pub fn hash(bytes: &[u8]) -> u64 {
+    xxh3_64(bytes)
+}
+
+/// Hash of `bytes` chained onto a previous hash
+pub fn chain(seed: u64, bytes: &[u8]) -> u64 {
+    xxh3_64_with_seed(bytes, seed)
+}
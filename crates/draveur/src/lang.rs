@@ -9,6 +9,11 @@ pub trait Lang {
 
     fn language() -> tree_sitter::Language;
 
+    /// Named snippet rule files can pull in with `;; @include <name>`, see [`crate::rules`]
+    fn fragment(_name: &str) -> Option<String> {
+        None
+    }
+
     fn build_query(s_expr: String) -> Result<Query> {
         let lang = Self::language();
         let query = Query::new(&lang, &s_expr).map_err(|e| TreeSitterError::Query(e, s_expr))?;
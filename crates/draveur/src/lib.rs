@@ -1,10 +1,23 @@
+pub mod cache;
+pub mod cancel;
+pub mod config;
 pub mod crawl;
+pub mod document;
 pub mod draveur;
 pub mod errors;
+pub mod hash;
 pub mod lang;
 pub mod parse;
+pub mod render;
+pub mod resolve;
+pub mod rules;
+pub mod testing;
+pub mod traverse;
 pub mod types;
+pub mod watch;
 
-pub use errors::{Error, IoErrorKind, Result, TreeSitterError};
+pub use errors::{
+    Diagnostic, Error, ExecutionFailure, IoErrorKind, Result, TreeSitterError, report,
+};
 pub use lang::Lang;
 pub use types::*;
@@ -45,12 +45,21 @@ impl<'a, 'tree> Noeud<'a, 'tree> {
     }
 
     pub fn parse(&self, query: &'a Query) -> NoeudIter<'a, 'tree> {
+        self.parse_within(query, 0)
+    }
+
+    /// Matches of `query` found within `timeout_micros`, 0 meaning no limit.
+    ///
+    /// Matches simply stop once the time is up, callers tell it from the end of the matches.
+    pub fn parse_within(&self, query: &'a Query, timeout_micros: u64) -> NoeudIter<'a, 'tree> {
         let Noeud { node, src: ctx } = self.clone();
+        let mut cursor = QueryCursor::new();
+        cursor.set_timeout_micros(timeout_micros);
 
         NoeudIterBuilder {
             query,
             src: ctx,
-            builder: QueryCursor::new(),
+            builder: cursor,
             cursor_builder: |builder, q| builder.matches(q, node, ctx),
         }
         .build()
@@ -0,0 +1,412 @@
+//! Box-drawing diagrams for the terminal
+//!
+//! Edges crossing without meeting are marked with `╫`, `┼` is left to edges that branch.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::render::layout::{Layered, Vertex};
+use crate::render::{Render, label};
+use crate::types::{Graph, NodeId};
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+// horizontal space between two vertices of a layer
+const GAP: usize = 2;
+// room left on the right for edge labels
+const MARGIN: usize = 12;
+
+#[derive(Debug, Clone)]
+pub struct Ascii {
+    /// Width the diagram should fit in, labels are truncated to get there
+    pub max_width: usize,
+    /// Longest label drawn before truncating
+    pub max_label: usize,
+    /// Also draw `_parent` back-edges
+    pub show_parents: bool,
+}
+
+impl Default for Ascii {
+    fn default() -> Self {
+        Self {
+            max_width: 100,
+            max_label: 40,
+            show_parents: false,
+        }
+    }
+}
+
+impl Render for Ascii {
+    fn render<W: Write>(&self, graphs: &[Graph], w: &mut W) -> fmt::Result {
+        // edges into other graphs name their sink and the file it's in
+        let mut sinks = HashMap::new();
+        for graph in graphs {
+            let file = graph
+                .root()
+                .and_then(|root| root.get("filename"))
+                .and_then(|v| v.as_str());
+            for node in graph.iter() {
+                sinks.insert(node.id(), (label(node), file));
+            }
+        }
+
+        for (i, graph) in graphs.iter().enumerate() {
+            let Some(root) = graph.root() else { continue };
+            if i > 0 {
+                writeln!(w)?;
+            }
+
+            let file = root.get("filename").and_then(|v| v.as_str());
+            match file {
+                Some(file) => writeln!(w, "── {} ({file})", label(root))?,
+                None => writeln!(w, "── {}", label(root))?,
+            }
+            self.graph(graph, &sinks, w)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ascii {
+    fn graph<W: Write>(
+        &self,
+        graph: &Graph,
+        sinks: &HashMap<NodeId, (String, Option<&str>)>,
+        w: &mut W,
+    ) -> fmt::Result {
+        let layout = Layered::new(graph, self.show_parents);
+        let labels = layout.nodes.iter().map(|n| label(n)).collect::<Vec<_>>();
+
+        // shrink labels until the widest layer fits
+        let mut cap = self.max_label.max(1);
+        let width = |cap: usize| {
+            layout
+                .layers
+                .iter()
+                .map(|layer| layer_width(layer, &labels, cap))
+                .max()
+                .unwrap_or(0)
+        };
+        while cap > 1 && width(cap) > self.max_width.saturating_sub(MARGIN) {
+            cap -= 1;
+        }
+        let labels = labels.iter().map(|l| truncate(l, cap)).collect::<Vec<_>>();
+
+        let total = width(cap);
+
+        // x of each vertex and its center
+        let mut xs = vec![];
+        for layer in &layout.layers {
+            let mut x = (total - layer_width(layer, &labels, usize::MAX)) / 2;
+            let mut row = vec![];
+            for vertex in layer {
+                let w = vertex_width(vertex, &labels, usize::MAX);
+                row.push((x, x + w / 2));
+                x += w + GAP;
+            }
+            xs.push(row);
+        }
+
+        // segments between consecutive layers: (from, to, edge label if last hop, arrowhead,
+        // route)
+        let mut gaps = vec![vec![]; layout.layers.len().saturating_sub(1)];
+        for (r, route) in layout.routes.iter().enumerate() {
+            let hops = route.points.len() - 1;
+            for (h, pair) in route.points.windows(2).enumerate() {
+                let (l, p) = pair[0];
+                let (_, q) = pair[1];
+                let last = match route.reversed {
+                    true => h == 0,
+                    false => h + 1 == hops,
+                };
+                let kind = route.edge.kind().filter(|_| last);
+                let head = match (route.reversed, last) {
+                    (true, true) => Some('▲'),
+                    (false, true) => Some('▼'),
+                    (_, false) => None,
+                };
+                gaps[l].push((xs[l][p].1, xs[l + 1][q].1, kind, head, r));
+            }
+        }
+
+        // rows: 3 per layer, then per gap a stub row, one track per bend and an arrow row
+        let mut tops = vec![];
+        let mut y = 0;
+        for (l, _) in layout.layers.iter().enumerate() {
+            tops.push(y);
+            y += 3;
+            if let Some(gap) = gaps.get(l) {
+                let tracks = gap.iter().filter(|(a, b, ..)| a != b).count().max(1);
+                y += tracks + 2;
+            }
+        }
+
+        let mut canvas = Canvas::new(total + MARGIN, y);
+
+        for (l, layer) in layout.layers.iter().enumerate() {
+            for (p, vertex) in layer.iter().enumerate() {
+                let (x, center) = xs[l][p];
+                match vertex {
+                    Vertex::Node(n) => canvas.boxed(x, tops[l], &labels[*n]),
+                    Vertex::Dummy => canvas.vline(center, tops[l], tops[l] + 2, true, true),
+                }
+            }
+        }
+
+        let mut arrows = vec![];
+        let mut labels_at = vec![];
+        for (l, gap) in gaps.iter().enumerate() {
+            let stub = tops[l] + 3;
+            let arrow = tops[l + 1] - 1;
+            let mut track = stub + 1;
+            let mut spans = vec![];
+
+            for &(from, to, kind, head, r) in gap {
+                let row = match from == to {
+                    true => stub + 1,
+                    false => {
+                        track += 1;
+                        track - 1
+                    }
+                };
+
+                if matches!(layout.layers[l][position(&xs[l], from)], Vertex::Node(_)) {
+                    canvas.set(from, stub - 1, '┬');
+                }
+                canvas.vline(from, stub, row, true, from == to);
+                canvas.hline(row, from, to);
+                canvas.vline(to, row, arrow, from == to, true);
+                spans.push((from, to, row));
+
+                match head {
+                    Some('▲') => arrows.push((from, stub, '▲')),
+                    Some(head) => arrows.push((to, arrow, head)),
+                    None => {}
+                }
+                if let Some(kind) = kind {
+                    // edges meeting on their way in can't tell their labels apart
+                    let shared = gap.iter().filter(|&&(_, other, ..)| other == to).count() > 1;
+                    labels_at.push((row, from.max(to) + 2, kind, shared, r));
+                }
+            }
+
+            // a bend passing over the upright of edges that neither leave from nor go to
+            // where it does crosses them
+            for &(from, to, row) in spans.iter().filter(|(from, to, _)| from != to) {
+                for x in from.min(to) + 1..from.max(to) {
+                    let uprights = spans.iter().filter(|&&(f, t, r)| match f == t {
+                        true => f == x,
+                        false => (f == x && row < r) || (t == x && r < row),
+                    });
+                    let mut uprights = uprights.peekable();
+                    if uprights.peek().is_some() && uprights.all(|&(f, t, _)| f != from && t != to)
+                    {
+                        canvas.cross(x, row);
+                    }
+                }
+            }
+        }
+
+        for (x, y, c) in arrows {
+            canvas.arrow(x, y, c);
+        }
+
+        // labels go last so they never get drawn over, the ones without room are listed
+        let mut listed = vec![];
+        for (row, x, kind, shared, r) in labels_at {
+            if shared || !canvas.text(x, row, kind) {
+                listed.push((r, kind));
+            }
+        }
+
+        write!(w, "{canvas}")?;
+
+        // edges whose label has no room in the diagram
+        let end = |(l, p): (usize, usize)| match layout.layers[l][p] {
+            Vertex::Node(n) => labels[n].as_str(),
+            Vertex::Dummy => "",
+        };
+        for (r, kind) in listed {
+            let route = &layout.routes[r];
+            let (mut source, mut sink) = (route.points[0], route.points[route.points.len() - 1]);
+            if route.reversed {
+                (source, sink) = (sink, source);
+            }
+            writeln!(w, "  {} ─{kind}→ {}", end(source), end(sink))?;
+        }
+
+        // edges to other graphs, e.g. `resolves_to`
+        let ids = graph.ids();
+        for node in graph.iter() {
+            for edge in node.edges() {
+                if ids.contains(edge.sink()) || (edge.is_parent() && !self.show_parents) {
+                    continue;
+                }
+                let sink = match sinks.get(&edge.sink()) {
+                    Some((sink, Some(file))) => format!("{} ({file})", truncate(sink, cap)),
+                    Some((sink, None)) => truncate(sink, cap),
+                    None => "?".to_string(),
+                };
+                writeln!(
+                    w,
+                    "  {} ─{}→ {sink}",
+                    truncate(&label(node), cap),
+                    edge.kind().unwrap_or(""),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn position(row: &[(usize, usize)], center: usize) -> usize {
+    row.iter().position(|&(_, c)| c == center).unwrap_or(0)
+}
+
+fn vertex_width(vertex: &Vertex, labels: &[String], cap: usize) -> usize {
+    match vertex {
+        Vertex::Node(n) => labels[*n].chars().count().min(cap) + 4,
+        Vertex::Dummy => 1,
+    }
+}
+
+fn layer_width(layer: &[Vertex], labels: &[String], cap: usize) -> usize {
+    let widths = layer.iter().map(|v| vertex_width(v, labels, cap));
+    widths.sum::<usize>() + GAP * layer.len().saturating_sub(1)
+}
+
+fn truncate(s: &str, cap: usize) -> String {
+    match s.chars().count() > cap {
+        true => s.chars().take(cap - 1).chain(['…']).collect(),
+        false => s.to_string(),
+    }
+}
+
+struct Canvas {
+    chars: Vec<Vec<char>>,
+    lines: Vec<Vec<u8>>,
+    crossings: Vec<Vec<bool>>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            chars: vec![vec![' '; width]; height],
+            lines: vec![vec![0; width]; height],
+            crossings: vec![vec![false; width]; height],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, c: char) {
+        if let Some(cell) = self.chars.get_mut(y).and_then(|r| r.get_mut(x)) {
+            *cell = c;
+        }
+    }
+
+    fn connect(&mut self, x: usize, y: usize, mask: u8) {
+        if let Some(cell) = self.lines.get_mut(y).and_then(|r| r.get_mut(x)) {
+            *cell |= mask;
+        }
+    }
+
+    fn cross(&mut self, x: usize, y: usize) {
+        if let Some(cell) = self.crossings.get_mut(y).and_then(|r| r.get_mut(x)) {
+            *cell = true;
+        }
+    }
+
+    fn arrow(&mut self, x: usize, y: usize, c: char) {
+        if let Some(cell) = self.lines.get_mut(y).and_then(|r| r.get_mut(x)) {
+            *cell = 0;
+        }
+        self.set(x, y, c);
+    }
+
+    fn vline(&mut self, x: usize, y0: usize, y1: usize, open_top: bool, open_bottom: bool) {
+        for y in y0..=y1 {
+            let mut mask = 0;
+            if y > y0 || open_top {
+                mask |= UP;
+            }
+            if y < y1 || open_bottom {
+                mask |= DOWN;
+            }
+            self.connect(x, y, mask);
+        }
+    }
+
+    fn hline(&mut self, y: usize, x0: usize, x1: usize) {
+        let (a, b) = (x0.min(x1), x0.max(x1));
+        for x in a..=b {
+            let mut mask = 0;
+            if x > a {
+                mask |= LEFT;
+            }
+            if x < b {
+                mask |= RIGHT;
+            }
+            self.connect(x, y, mask);
+        }
+    }
+
+    fn boxed(&mut self, x: usize, y: usize, label: &str) {
+        let inner = label.chars().count() + 2;
+        let border = "─".repeat(inner);
+        self.text(x, y, &format!("┌{border}┐"));
+        self.text(x, y + 1, &format!("│ {label} │"));
+        self.text(x, y + 2, &format!("└{border}┘"));
+    }
+
+    /// Writes text over empty cells only, whether there was room for it
+    fn text(&mut self, x: usize, y: usize, s: &str) -> bool {
+        let len = s.chars().count();
+        let free = (x..x + len).all(|x| {
+            self.lines.get(y).and_then(|r| r.get(x)) == Some(&0)
+                && self.chars.get(y).and_then(|r| r.get(x)) == Some(&' ')
+        });
+        if free {
+            for (i, c) in s.chars().enumerate() {
+                self.set(x + i, y, c);
+            }
+        }
+        free
+    }
+}
+
+impl fmt::Display for Canvas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for ((chars, lines), crossings) in self.chars.iter().zip(&self.lines).zip(&self.crossings) {
+            let row = chars
+                .iter()
+                .zip(lines)
+                .zip(crossings)
+                .map(|((&c, &mask), &crossing)| match mask {
+                    0 => c,
+                    m if crossing && m == UP | DOWN | LEFT | RIGHT => '╫',
+                    _ => junction(mask),
+                })
+                .collect::<String>();
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+fn junction(mask: u8) -> char {
+    match mask {
+        m if m == UP | DOWN | LEFT | RIGHT => '┼',
+        m if m == UP | DOWN | RIGHT => '├',
+        m if m == UP | DOWN | LEFT => '┤',
+        m if m == DOWN | LEFT | RIGHT => '┬',
+        m if m == UP | LEFT | RIGHT => '┴',
+        m if m == DOWN | RIGHT => '┌',
+        m if m == DOWN | LEFT => '┐',
+        m if m == UP | RIGHT => '└',
+        m if m == UP | LEFT => '┘',
+        m if m & (LEFT | RIGHT) != 0 && m & (UP | DOWN) == 0 => '─',
+        _ => '│',
+    }
+}
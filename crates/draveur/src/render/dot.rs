@@ -0,0 +1,114 @@
+//! [Graphviz](https://graphviz.org/doc/info/lang.html) DOT graphs
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use crate::render::{Render, label};
+use crate::types::{Edge, Graph, Node};
+
+#[derive(Debug, Default, Clone)]
+pub struct Dot {
+    /// Also draw `_parent` back-edges
+    pub show_parents: bool,
+}
+
+impl Dot {
+    fn node<W: Write>(&self, node: &Node, indent: &str, w: &mut W) -> fmt::Result {
+        let style = match node.node_type() {
+            Some("function_definition") => {
+                r##"shape=box, style="rounded,filled", fillcolor="#dbeafe""##
+            }
+            Some("class_definition") => r##"shape=component, style=filled, fillcolor="#fde68a""##,
+            Some("call") => r##"shape=ellipse, style=filled, fillcolor="#e5e7eb""##,
+            Some("if_statement" | "elif_clause" | "else_clause") => {
+                r##"shape=diamond, style=filled, fillcolor="#fce7f3""##
+            }
+            _ => "shape=box",
+        };
+        writeln!(
+            w,
+            "{indent}n{} [label=\"{}\", {style}];",
+            node.id(),
+            escape(&label(node))
+        )
+    }
+
+    fn edge<W: Write>(&self, source: &Node, edge: &Edge, w: &mut W) -> fmt::Result {
+        let style = match edge.kind() {
+            Some("method") => "style=bold",
+            Some("resolves_to") => r##"style=dashed, color="#2563eb""##,
+            Some("entry" | "if" | "elif" | "else") => r##"color="#6b7280""##,
+            Some("_parent") => r##"style=dotted, arrowhead=empty, color="#9ca3af""##,
+            _ => "style=solid",
+        };
+        match edge.kind() {
+            Some(kind) => writeln!(
+                w,
+                "  n{} -> n{} [label=\"{}\", {style}];",
+                source.id(),
+                edge.sink(),
+                escape(kind)
+            ),
+            None => writeln!(w, "  n{} -> n{} [{style}];", source.id(), edge.sink()),
+        }
+    }
+}
+
+impl Render for Dot {
+    fn render<W: Write>(&self, graphs: &[Graph], w: &mut W) -> fmt::Result {
+        writeln!(w, "digraph draveur {{")?;
+        writeln!(w, "  compound=true;")?;
+        writeln!(w, "  node [fontname=\"Helvetica\"];")?;
+        writeln!(w, "  edge [fontname=\"Helvetica\", fontsize=10];")?;
+
+        // cluster subgraphs by file, in file order
+        let mut files: BTreeMap<&str, Vec<&Graph>> = BTreeMap::new();
+        for graph in graphs {
+            let Some(root) = graph.root() else { continue };
+            let file = root.get("filename").and_then(|v| v.as_str()).unwrap_or("");
+            files.entry(file).or_default().push(graph);
+        }
+
+        for (i, (file, group)) in files.iter().enumerate() {
+            writeln!(w, "  subgraph cluster_file_{i} {{")?;
+            writeln!(w, "    label=\"{}\";", escape(file))?;
+            writeln!(w, "    style=dashed;")?;
+
+            for graph in group {
+                let Some(root) = graph.root() else { continue };
+
+                if root.node_type() == Some("class_definition") {
+                    writeln!(w, "    subgraph cluster_g{} {{", root.id())?;
+                    writeln!(w, "      label=\"{}\";", escape(&label(root)))?;
+                    writeln!(w, "      style=\"rounded,filled\";")?;
+                    writeln!(w, "      fillcolor=\"#fffbeb\";")?;
+                    for node in graph.iter() {
+                        self.node(node, "      ", w)?;
+                    }
+                    writeln!(w, "    }}")?;
+                } else {
+                    for node in graph.iter() {
+                        self.node(node, "    ", w)?;
+                    }
+                }
+            }
+            writeln!(w, "  }}")?;
+        }
+
+        for graph in graphs {
+            for node in graph.iter() {
+                for edge in node.edges() {
+                    if edge.is_parent() && !self.show_parents {
+                        continue;
+                    }
+                    self.edge(node, edge, w)?;
+                }
+            }
+        }
+        writeln!(w, "}}")
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,242 @@
+//! Layered (Sugiyama-style) layout of a single graph.
+//!
+//! 1. break cycles by reversing DFS back-edges
+//! 2. assign layers by longest path from the sources
+//! 3. split edges spanning several layers with dummy vertices
+//! 4. reduce crossings with a few barycenter sweeps
+
+use std::collections::HashMap;
+
+use crate::types::{Edge, Graph, Node};
+
+const SWEEPS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Vertex {
+    /// Index of the node in the graph
+    Node(usize),
+    /// Bend point of an edge spanning several layers
+    Dummy,
+}
+
+#[derive(Debug)]
+pub(crate) struct Route<'g> {
+    pub edge: &'g Edge,
+    /// `(layer, position)` of every vertex crossed, from the upper to the lower end
+    pub points: Vec<(usize, usize)>,
+    /// The edge points upwards, i.e. the arrow goes on the first point
+    pub reversed: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct Layered<'g> {
+    pub nodes: Vec<&'g Node>,
+    pub layers: Vec<Vec<Vertex>>,
+    pub routes: Vec<Route<'g>>,
+}
+
+impl<'g> Layered<'g> {
+    pub fn new(graph: &'g Graph, show_parents: bool) -> Self {
+        let nodes = graph.iter().collect::<Vec<_>>();
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.id(), i))
+            .collect::<HashMap<_, _>>();
+
+        // (source, sink, edge), leaving out self loops and edges to other graphs
+        let edges = nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, node)| node.edges().iter().map(move |e| (i, e)))
+            .filter(|(_, e)| show_parents || !e.is_parent())
+            .filter_map(|(i, e)| index.get(&e.sink()).map(|&j| (i, j, e)))
+            .filter(|(i, j, _)| i != j)
+            .collect::<Vec<_>>();
+
+        let reversed = back_edges(nodes.len(), &edges);
+        let dag = edges
+            .iter()
+            .zip(&reversed)
+            .map(|(&(i, j, _), &rev)| if rev { (j, i) } else { (i, j) })
+            .collect::<Vec<_>>();
+
+        let rank = longest_path(nodes.len(), &dag);
+        let depth = rank.iter().max().map_or(0, |r| r + 1);
+
+        // vertex ids: nodes first, then dummies
+        let mut vertices = (0..nodes.len()).map(Vertex::Node).collect::<Vec<_>>();
+        let mut vertex_rank = rank.clone();
+        let mut chains = vec![];
+
+        for &(top, bottom) in &dag {
+            let mut chain = vec![top];
+            for r in rank[top] + 1..rank[bottom] {
+                vertices.push(Vertex::Dummy);
+                vertex_rank.push(r);
+                chain.push(vertices.len() - 1);
+            }
+            chain.push(bottom);
+            chains.push(chain);
+        }
+
+        let mut layers = vec![vec![]; depth];
+        for (v, &r) in vertex_rank.iter().enumerate() {
+            layers[r].push(v);
+        }
+
+        order(&mut layers, &chains, vertices.len());
+
+        let mut position = vec![(0, 0); vertices.len()];
+        for (l, layer) in layers.iter().enumerate() {
+            for (p, &v) in layer.iter().enumerate() {
+                position[v] = (l, p);
+            }
+        }
+
+        let routes = edges
+            .iter()
+            .zip(reversed)
+            .zip(&chains)
+            .map(|((&(_, _, edge), reversed), chain)| Route {
+                edge,
+                points: chain.iter().map(|&v| position[v]).collect(),
+                reversed,
+            })
+            .collect();
+
+        let layers = layers
+            .into_iter()
+            .map(|layer| layer.into_iter().map(|v| vertices[v]).collect())
+            .collect();
+
+        Self {
+            nodes,
+            layers,
+            routes,
+        }
+    }
+}
+
+/// Marks the edges closing a cycle during a depth-first traversal
+fn back_edges(n: usize, edges: &[(usize, usize, &Edge)]) -> Vec<bool> {
+    let mut out = vec![vec![]; n];
+    for (e, &(i, j, _)) in edges.iter().enumerate() {
+        out[i].push((j, e));
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        Active,
+        Done,
+    }
+
+    let mut state = vec![State::New; n];
+    let mut reversed = vec![false; edges.len()];
+
+    for start in 0..n {
+        if state[start] != State::New {
+            continue;
+        }
+        state[start] = State::Active;
+        let mut stack = vec![(start, 0)];
+
+        while let Some((v, next)) = stack.last_mut() {
+            let v = *v;
+            match out[v].get(*next) {
+                Some(&(w, e)) => {
+                    *next += 1;
+                    match state[w] {
+                        State::New => {
+                            state[w] = State::Active;
+                            stack.push((w, 0));
+                        }
+                        State::Active => reversed[e] = true,
+                        State::Done => {}
+                    }
+                }
+                None => {
+                    state[v] = State::Done;
+                    stack.pop();
+                }
+            }
+        }
+    }
+    reversed
+}
+
+/// Layer of each vertex in a DAG, sources on layer 0
+fn longest_path(n: usize, dag: &[(usize, usize)]) -> Vec<usize> {
+    let mut indegree = vec![0; n];
+    let mut out = vec![vec![]; n];
+    for &(i, j) in dag {
+        indegree[j] += 1;
+        out[i].push(j);
+    }
+
+    let mut rank = vec![0; n];
+    let mut queue = (0..n).filter(|&v| indegree[v] == 0).collect::<Vec<_>>();
+
+    while let Some(v) = queue.pop() {
+        for &w in &out[v] {
+            rank[w] = rank[w].max(rank[v] + 1);
+            indegree[w] -= 1;
+            if indegree[w] == 0 {
+                queue.push(w);
+            }
+        }
+    }
+    rank
+}
+
+/// Reorders vertices within their layer to reduce edge crossings
+fn order(layers: &mut [Vec<usize>], chains: &[Vec<usize>], n: usize) {
+    let mut up = vec![vec![]; n];
+    let mut down = vec![vec![]; n];
+    for chain in chains {
+        for pair in chain.windows(2) {
+            down[pair[0]].push(pair[1]);
+            up[pair[1]].push(pair[0]);
+        }
+    }
+
+    let mut position = vec![0.0; n];
+    let mut index = |layers: &[Vec<usize>]| {
+        for layer in layers {
+            for (p, &v) in layer.iter().enumerate() {
+                position[v] = p as f64;
+            }
+        }
+    };
+    index(layers);
+
+    for sweep in 0..SWEEPS {
+        let (range, neighbours): (Vec<usize>, _) = match sweep % 2 {
+            0 => ((1..layers.len()).collect(), &up),
+            _ => ((0..layers.len().saturating_sub(1)).rev().collect(), &down),
+        };
+
+        for l in range {
+            let barycenter = |v: usize| {
+                let adjacent = &neighbours[v];
+                match adjacent.is_empty() {
+                    true => position[v],
+                    false => {
+                        adjacent.iter().map(|&w| position[w]).sum::<f64>() / adjacent.len() as f64
+                    }
+                }
+            };
+            let mut keyed = layers[l]
+                .iter()
+                .map(|&v| (barycenter(v), v))
+                .collect::<Vec<_>>();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            layers[l] = keyed.into_iter().map(|(_, v)| v).collect();
+            for (p, &v) in layers[l].iter().enumerate() {
+                position[v] = p as f64;
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! [Mermaid](https://mermaid.js.org/syntax/flowchart.html) flowcharts
+
+use std::fmt::{self, Write};
+
+use crate::render::{Render, label};
+use crate::types::{Graph, Node};
+
+#[derive(Debug, Default, Clone)]
+pub struct Mermaid {
+    /// Also draw `_parent` back-edges
+    pub show_parents: bool,
+}
+
+impl Mermaid {
+    fn node<W: Write>(&self, node: &Node, w: &mut W) -> fmt::Result {
+        let label = escape(&label(node));
+        let id = node.id();
+
+        match node.node_type() {
+            Some("class_definition") => writeln!(w, "    n{id}[[\"{label}\"]]"),
+            Some("call") => writeln!(w, "    n{id}([\"{label}\"])"),
+            Some("if_statement" | "elif_clause" | "else_clause") => {
+                writeln!(w, "    n{id}{{\"{label}\"}}")
+            }
+            _ => writeln!(w, "    n{id}[\"{label}\"]"),
+        }
+    }
+}
+
+impl Render for Mermaid {
+    fn render<W: Write>(&self, graphs: &[Graph], w: &mut W) -> fmt::Result {
+        writeln!(w, "flowchart TD")?;
+
+        // edges leaving their subgraph, e.g. `resolves_to`
+        let mut foreign = vec![];
+
+        for graph in graphs {
+            let Some(root) = graph.root() else { continue };
+            let ids = graph.ids();
+
+            writeln!(
+                w,
+                "  subgraph g{} [\"{}\"]",
+                root.id(),
+                escape(&label(root))
+            )?;
+            for node in graph.iter() {
+                self.node(node, w)?;
+            }
+            for node in graph.iter() {
+                for edge in node.edges() {
+                    if edge.is_parent() && !self.show_parents {
+                        continue;
+                    }
+                    if !ids.contains(edge.sink()) {
+                        foreign.push((node.id(), edge));
+                        continue;
+                    }
+                    match edge.kind() {
+                        Some(kind) => {
+                            writeln!(w, "    n{} -->|{}| n{}", node.id(), kind, edge.sink())?
+                        }
+                        None => writeln!(w, "    n{} --> n{}", node.id(), edge.sink())?,
+                    }
+                }
+            }
+            writeln!(w, "  end")?;
+        }
+
+        for (source, edge) in foreign {
+            match edge.kind() {
+                Some(kind) => writeln!(w, "  n{} -.->|{}| n{}", source, kind, edge.sink())?,
+                None => writeln!(w, "  n{} -.-> n{}", source, edge.sink())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
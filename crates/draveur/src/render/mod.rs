@@ -0,0 +1,48 @@
+//! Renders graphs as diagrams.
+//!
+//! Every renderer draws one cluster per subgraph (i.e. per matched function or class) and labels
+//! edges with their `kind` attribute. `_parent` back-edges are left out unless asked for.
+
+use std::fmt;
+
+use crate::types::{Graph, Node};
+
+mod ascii;
+mod dot;
+mod layout;
+mod mermaid;
+mod svg;
+
+pub use ascii::Ascii;
+pub use dot::Dot;
+pub use mermaid::Mermaid;
+pub use svg::Svg;
+
+pub trait Render {
+    fn render<W: fmt::Write>(&self, graphs: &[Graph], w: &mut W) -> fmt::Result;
+
+    fn to_string(&self, graphs: &[Graph]) -> String {
+        let mut out = String::new();
+        // writing to a String never fails
+        let _ = self.render(graphs, &mut out);
+        out
+    }
+}
+
+/// Single-line display label of a node
+pub fn label(node: &Node) -> String {
+    let condition = node.get("condition").and_then(|v| v.as_str());
+
+    let label = match (node.node_type(), condition) {
+        (Some("if_statement"), Some(cond)) => format!("if {cond}"),
+        (Some("elif_clause"), Some(cond)) => format!("elif {cond}"),
+        (Some("else_clause"), _) => "else".to_string(),
+        _ => node
+            .name()
+            .or(node.node_type())
+            .unwrap_or_default()
+            .to_string(),
+    };
+
+    label.split_whitespace().collect::<Vec<_>>().join(" ")
+}
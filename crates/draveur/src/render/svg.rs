@@ -0,0 +1,374 @@
+//! Standalone SVG documents, no browser or javascript needed
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::render::layout::{Layered, Vertex};
+use crate::render::{Render, label};
+use crate::types::{Edge, Graph, Node, NodeId};
+
+const NODE_HEIGHT: f64 = 36.0;
+const DUMMY_WIDTH: f64 = 8.0;
+const CHAR_WIDTH: f64 = 7.2;
+const H_GAP: f64 = 32.0;
+const V_GAP: f64 = 64.0;
+const PADDING: f64 = 24.0;
+const TITLE: f64 = 28.0;
+// room on the right for edges between graphs
+const MARGIN: f64 = 48.0;
+
+const STYLE: &str = r#"
+  text { font-family: ui-monospace, Menlo, Consolas, monospace; font-size: 12px; fill: #111827; }
+  .title { font-size: 13px; font-weight: bold; }
+  .file { fill: #6b7280; font-weight: normal; }
+  .panel { fill: #f9fafb; stroke: #d1d5db; }
+  .node rect { stroke: #374151; stroke-width: 1; }
+  .function_definition rect { fill: #dbeafe; }
+  .class_definition rect { fill: #fde68a; }
+  .call rect { fill: #e5e7eb; }
+  .if_statement rect, .elif_clause rect, .else_clause rect { fill: #fce7f3; }
+  .icon { font-weight: bold; fill: #ffffff; }
+  .icon-bg { fill: #374151; }
+  .edge { fill: none; stroke: #4b5563; stroke-width: 1.2; }
+  .edge.resolves_to { stroke: #2563eb; stroke-dasharray: 5 3; }
+  .edge._parent { stroke: #9ca3af; stroke-dasharray: 2 3; }
+  .edge.entry, .edge.if, .edge.elif, .edge.else { stroke: #9d174d; }
+  .label { font-size: 10px; fill: #4b5563; }
+"#;
+
+#[derive(Debug, Default, Clone)]
+pub struct Svg {
+    /// Also draw `_parent` back-edges
+    pub show_parents: bool,
+}
+
+/// Laid out box of a graph node
+#[derive(Clone, Copy)]
+struct Frame {
+    x: f64,
+    y: f64,
+    w: f64,
+}
+
+impl Frame {
+    fn right(&self) -> (f64, f64) {
+        (self.x + self.w, self.y + NODE_HEIGHT / 2.0)
+    }
+}
+
+fn node_width(label: &str) -> f64 {
+    (label.chars().count() as f64 * CHAR_WIDTH + 44.0).max(64.0)
+}
+
+fn icon(node: &Node) -> &'static str {
+    match node.node_type() {
+        Some("function_definition") => "ƒ",
+        Some("class_definition") => "C",
+        Some("call") => "→",
+        Some("if_statement" | "elif_clause" | "else_clause") => "?",
+        _ => "•",
+    }
+}
+
+struct Panel<'g> {
+    graph: &'g Graph,
+    layout: Layered<'g>,
+    labels: Vec<String>,
+    // (x, center) of each vertex per layer
+    xs: Vec<Vec<(f64, f64)>>,
+    width: f64,
+    height: f64,
+}
+
+impl<'g> Panel<'g> {
+    fn new(graph: &'g Graph, show_parents: bool) -> Self {
+        let layout = Layered::new(graph, show_parents);
+        let labels = layout.nodes.iter().map(|n| label(n)).collect::<Vec<_>>();
+
+        let vertex_width = |v: &Vertex| match v {
+            Vertex::Node(n) => node_width(&labels[*n]),
+            Vertex::Dummy => DUMMY_WIDTH,
+        };
+        let layer_width = |layer: &[Vertex]| {
+            layer.iter().map(vertex_width).sum::<f64>()
+                + H_GAP * layer.len().saturating_sub(1) as f64
+        };
+
+        let width = layout
+            .layers
+            .iter()
+            .map(|l| layer_width(l))
+            .fold(0.0, f64::max);
+
+        let xs = layout
+            .layers
+            .iter()
+            .map(|layer| {
+                let mut x = (width - layer_width(layer)) / 2.0;
+                layer
+                    .iter()
+                    .map(|v| {
+                        let w = vertex_width(v);
+                        let pos = (x, x + w / 2.0);
+                        x += w + H_GAP;
+                        pos
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let layers = layout.layers.len() as f64;
+        let height = layers * NODE_HEIGHT + (layers - 1.0).max(0.0) * V_GAP;
+
+        Self {
+            graph,
+            layout,
+            labels,
+            xs,
+            width,
+            height,
+        }
+    }
+
+    fn y(&self, layer: usize) -> f64 {
+        layer as f64 * (NODE_HEIGHT + V_GAP)
+    }
+
+    fn frames(&self, ox: f64, oy: f64) -> HashMap<NodeId, Frame> {
+        let mut frames = HashMap::new();
+        for (l, layer) in self.layout.layers.iter().enumerate() {
+            for (p, vertex) in layer.iter().enumerate() {
+                if let Vertex::Node(n) = vertex {
+                    let (x, _) = self.xs[l][p];
+                    let frame = Frame {
+                        x: ox + x,
+                        y: oy + self.y(l),
+                        w: node_width(&self.labels[*n]),
+                    };
+                    frames.insert(self.layout.nodes[*n].id(), frame);
+                }
+            }
+        }
+        frames
+    }
+
+    fn render<W: Write>(
+        &self,
+        frames: &HashMap<NodeId, Frame>,
+        ox: f64,
+        oy: f64,
+        w: &mut W,
+    ) -> fmt::Result {
+        for route in &self.layout.routes {
+            let points = route
+                .points
+                .iter()
+                .enumerate()
+                .map(|(i, &(l, p))| {
+                    let center = ox + self.xs[l][p].1;
+                    let top = oy + self.y(l);
+                    match (i, self.layout.layers[l][p]) {
+                        (0, _) => (center, top + NODE_HEIGHT),
+                        (i, Vertex::Node(_)) if i + 1 == route.points.len() => (center, top),
+                        _ => (center, top + NODE_HEIGHT / 2.0),
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let mut d = format!("M {:.1} {:.1}", points[0].0, points[0].1);
+            for pair in points.windows(2) {
+                let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                let my = (y0 + y1) / 2.0;
+                write!(d, " C {x0:.1} {my:.1}, {x1:.1} {my:.1}, {x1:.1} {y1:.1}")?;
+            }
+
+            let marker = match route.reversed {
+                true => "marker-start=\"url(#arrow-start)\"",
+                false => "marker-end=\"url(#arrow)\"",
+            };
+            edge_path(route.edge, &d, marker, w)?;
+
+            // label the hop entering the arrow's target
+            if let Some(kind) = route.edge.kind() {
+                let (a, b) = match route.reversed {
+                    true => (points[1], points[0]),
+                    false => (points[points.len() - 2], points[points.len() - 1]),
+                };
+                let (x, y) = ((a.0 + b.0) / 2.0 + 4.0, (a.1 + b.1) / 2.0);
+                writeln!(
+                    w,
+                    r#"  <text class="label" x="{x:.1}" y="{y:.1}">{}</text>"#,
+                    escape(kind)
+                )?;
+            }
+        }
+
+        for node in self.graph.iter() {
+            let Some(frame) = frames.get(&node.id()) else {
+                continue;
+            };
+            self.node(node, frame, w)?;
+        }
+        Ok(())
+    }
+
+    fn node<W: Write>(&self, node: &Node, frame: &Frame, w: &mut W) -> fmt::Result {
+        let class = node.node_type().unwrap_or("node");
+        let label = label(node);
+
+        writeln!(w, r#"  <g class="node {}">"#, escape(class))?;
+        writeln!(w, "    <title>{}</title>", escape(&tooltip(node, &label)))?;
+        writeln!(
+            w,
+            r#"    <rect x="{:.1}" y="{:.1}" width="{:.1}" height="{NODE_HEIGHT}" rx="6"/>"#,
+            frame.x, frame.y, frame.w
+        )?;
+        let (cx, cy) = (frame.x + 16.0, frame.y + NODE_HEIGHT / 2.0);
+        writeln!(
+            w,
+            r#"    <circle class="icon-bg" cx="{cx:.1}" cy="{cy:.1}" r="9"/>"#
+        )?;
+        writeln!(
+            w,
+            r#"    <text class="icon" x="{cx:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+            cy + 4.0,
+            icon(node)
+        )?;
+        writeln!(
+            w,
+            r#"    <text x="{:.1}" y="{:.1}">{}</text>"#,
+            frame.x + 32.0,
+            cy + 4.0,
+            escape(&label)
+        )?;
+        writeln!(w, "  </g>")
+    }
+}
+
+fn tooltip(node: &Node, label: &str) -> String {
+    let mut tip = format!("{} {label}", node.node_type().unwrap_or("node"));
+
+    let row = |k: &str| node.get(k).and_then(|v| v.as_int());
+    if let (Some(start), Some(end)) = (row("start_row"), row("end_row")) {
+        // rows are 0-based
+        let _ = write!(tip, "\nlines {}-{}", start + 1, end + 1);
+    }
+    if let Some(file) = node.get("filename").and_then(|v| v.as_str()) {
+        let _ = write!(tip, "\n{file}");
+    }
+    if let Some(decorator) = node.get("decorator").and_then(|v| v.as_str()) {
+        let _ = write!(tip, "\n@{decorator}");
+    }
+    tip
+}
+
+fn edge_path<W: Write>(edge: &Edge, d: &str, marker: &str, w: &mut W) -> fmt::Result {
+    writeln!(
+        w,
+        r#"  <path class="edge {}" d="{d}" {marker}/>"#,
+        escape(edge.kind().unwrap_or(""))
+    )
+}
+
+impl Render for Svg {
+    fn render<W: Write>(&self, graphs: &[Graph], w: &mut W) -> fmt::Result {
+        let panels = graphs
+            .iter()
+            .filter(|g| g.root().is_some())
+            .map(|g| Panel::new(g, self.show_parents))
+            .collect::<Vec<_>>();
+
+        let inner = panels.iter().map(|p| p.width).fold(0.0, f64::max);
+        let width = inner + 2.0 * PADDING + MARGIN;
+
+        // vertical offset of each panel
+        let mut offsets = vec![];
+        let mut y = PADDING;
+        for panel in &panels {
+            offsets.push(y);
+            y += TITLE + panel.height + 2.0 * PADDING;
+        }
+        let height = y;
+
+        let mut frames = HashMap::new();
+        for (panel, &oy) in panels.iter().zip(&offsets) {
+            let ox = 2.0 * PADDING + (inner - panel.width) / 2.0;
+            frames.extend(panel.frames(ox, oy + TITLE + PADDING));
+        }
+
+        writeln!(
+            w,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.0}" height="{height:.0}" viewBox="0 0 {width:.0} {height:.0}">"#
+        )?;
+        writeln!(w, "<style>{STYLE}</style>")?;
+        writeln!(w, "<defs>")?;
+        writeln!(
+            w,
+            r##"  <marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="7" markerHeight="7" orient="auto"><path d="M 0 0 L 10 5 L 0 10 z" fill="#4b5563"/></marker>"##
+        )?;
+        writeln!(
+            w,
+            r##"  <marker id="arrow-start" viewBox="0 0 10 10" refX="0" refY="5" markerWidth="7" markerHeight="7" orient="auto"><path d="M 10 0 L 0 5 L 10 10 z" fill="#4b5563"/></marker>"##
+        )?;
+        writeln!(w, "</defs>")?;
+
+        for (panel, &oy) in panels.iter().zip(&offsets) {
+            let Some(root) = panel.graph.root() else {
+                continue;
+            };
+            let ox = 2.0 * PADDING + (inner - panel.width) / 2.0;
+
+            writeln!(
+                w,
+                r#"<rect class="panel" x="{PADDING}" y="{oy:.1}" width="{:.1}" height="{:.1}" rx="8"/>"#,
+                inner + 2.0 * PADDING,
+                TITLE + panel.height + PADDING * 1.5
+            )?;
+            write!(
+                w,
+                r#"<text class="title" x="{:.1}" y="{:.1}">{}"#,
+                PADDING + 12.0,
+                oy + 20.0,
+                escape(&label(root))
+            )?;
+            if let Some(file) = root.get("filename").and_then(|v| v.as_str()) {
+                write!(w, r#" <tspan class="file">{}</tspan>"#, escape(file))?;
+            }
+            writeln!(w, "</text>")?;
+
+            panel.render(&frames, ox, oy + TITLE + PADDING, w)?;
+        }
+
+        // edges between graphs, routed through the right margin
+        let lane = PADDING * 2.0 + inner + MARGIN / 2.0;
+        for panel in &panels {
+            let ids = panel.graph.ids();
+            for node in panel.graph.iter() {
+                for edge in node.edges() {
+                    if ids.contains(edge.sink()) || (edge.is_parent() && !self.show_parents) {
+                        continue;
+                    }
+                    let (Some(a), Some(b)) = (frames.get(&node.id()), frames.get(&edge.sink()))
+                    else {
+                        continue;
+                    };
+                    let ((x0, y0), (x1, y1)) = (a.right(), b.right());
+                    let d = format!(
+                        "M {x0:.1} {y0:.1} C {lane:.1} {y0:.1}, {lane:.1} {y1:.1}, {x1:.1} {y1:.1}"
+                    );
+                    edge_path(edge, &d, "marker-end=\"url(#arrow)\"", w)?;
+                }
+            }
+        }
+
+        writeln!(w, "</svg>")
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,171 @@
+//! Links call nodes to the definitions they refer to across subgraphs.
+//!
+//! Resolution is name based: `foo(..)` resolves to functions (or classes) named `foo`,
+//! preferring the ones from the caller's file. `module.foo(..)` resolves to `foo` only in files
+//! that are `module`, e.g. `storage.py` or `pkg/storage/__init__.py` for `pkg.storage`, so calls
+//! into modules outside of the crawl such as `os.path.join(..)` stay unresolved. `self.foo(..)`
+//! and `cls.foo(..)` resolve to methods of the enclosing class.
+
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+use crate::types::{Edge, Graph, Node, NodeId};
+
+pub const RESOLVES_TO: &str = "resolves_to";
+
+struct Definition<'g> {
+    id: NodeId,
+    file: Option<&'g str>,
+}
+
+#[derive(Default)]
+struct Definitions<'g> {
+    // module-level functions and classes by name
+    globals: HashMap<&'g str, Vec<Definition<'g>>>,
+    // methods by (class id, name)
+    methods: HashMap<(NodeId, &'g str), NodeId>,
+    // call id -> enclosing function id
+    callers: HashMap<NodeId, NodeId>,
+    // method id -> class id
+    classes: HashMap<NodeId, NodeId>,
+}
+
+impl<'g> Definitions<'g> {
+    fn new(graphs: &'g [Graph]) -> Self {
+        let mut defs = Self::default();
+
+        for graph in graphs {
+            let file = graph.root().and_then(|r| r.get("filename")?.as_str());
+
+            for node in graph.iter() {
+                for edge in node.edges() {
+                    match edge.kind() {
+                        Some("call") => {
+                            defs.callers.insert(edge.sink(), node.id());
+                        }
+                        Some("method") => {
+                            defs.classes.insert(edge.sink(), node.id());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            for node in graph.iter() {
+                let Some(name) = node.name() else { continue };
+
+                match (node.node_type(), defs.classes.get(&node.id())) {
+                    (Some("function_definition"), Some(&class)) => {
+                        defs.methods.insert((class, name), node.id());
+                    }
+                    (Some("function_definition" | "class_definition"), None) => {
+                        defs.globals.entry(name).or_default().push(Definition {
+                            id: node.id(),
+                            file,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        defs
+    }
+
+    fn resolve(&self, call: &Node, file: Option<&str>) -> Vec<NodeId> {
+        let Some(name) = call.name() else {
+            return vec![];
+        };
+
+        // self.foo() / cls.foo() -> method of the enclosing class
+        if let Some(method) = name
+            .strip_prefix("self.")
+            .or_else(|| name.strip_prefix("cls."))
+        {
+            return self
+                .callers
+                .get(&call.id())
+                .and_then(|caller| self.classes.get(caller))
+                .and_then(|class| self.methods.get(&(*class, method)))
+                .map(|id| vec![*id])
+                .unwrap_or_default();
+        }
+
+        // module.foo() -> foo of that module
+        if let Some((module, name)) = name.rsplit_once('.') {
+            return self
+                .globals
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter(|d| d.file.is_some_and(|file| is_module(file, module)))
+                .map(|d| d.id)
+                .collect();
+        }
+
+        let Some(candidates) = self.globals.get(name) else {
+            return vec![];
+        };
+
+        let local = candidates
+            .iter()
+            .filter(|d| d.file.is_some() && d.file == file)
+            .map(|d| d.id)
+            .collect::<Vec<_>>();
+
+        match local.is_empty() {
+            true => candidates.iter().map(|d| d.id).collect(),
+            false => local,
+        }
+    }
+}
+
+/// Adds a `resolves_to` edge from each call node to the definitions it refers to.
+pub fn resolve_calls(graphs: &mut [Graph]) {
+    let resolved = {
+        let defs = Definitions::new(graphs);
+        let mut resolved = vec![];
+
+        for (g, graph) in graphs.iter().enumerate() {
+            let file = graph.root().and_then(|r| r.get("filename")?.as_str());
+
+            for (n, node) in graph.iter().enumerate() {
+                if node.node_type() != Some("call") {
+                    continue;
+                }
+                for sink in defs.resolve(node, file) {
+                    resolved.push((g, n, sink));
+                }
+            }
+        }
+        resolved
+    };
+
+    // `resolved` is ordered by graph then node
+    let mut resolved = resolved.into_iter().peekable();
+    for (g, graph) in graphs.iter_mut().enumerate() {
+        for (n, node) in graph.iter_mut().enumerate() {
+            while let Some((_, _, sink)) = resolved.next_if(|&(rg, rn, _)| (rg, rn) == (g, n)) {
+                node.add_edge(Edge::new(sink, RESOLVES_TO));
+            }
+        }
+    }
+}
+
+/// Whether `file` is the source of the dotted `module` name, the package `__init__` standing
+/// for its directory
+fn is_module(file: &str, module: &str) -> bool {
+    let path = Path::new(file).with_extension("");
+    let mut parts = path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if parts.last() == Some(&"__init__") {
+        parts.pop();
+    }
+
+    let module = module.split('.').collect::<Vec<_>>();
+    parts.ends_with(&module)
+}
@@ -0,0 +1,57 @@
+//! Rules read at runtime from tree-sitter query (`.scm`) and stanza (`.tsg`) files.
+//!
+//! A line `;; @include <name>` is replaced with the content of `<name>`: another `.scm` or
+//! `.tsg` file, relative to the including one, or a fragment built into the language (see
+//! [`Lang::fragment`]), e.g. `;; @include common_attributes`. Being a comment, the directive
+//! leaves files readable by other tree-sitter tools.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lang::Lang;
+use crate::{Error, IoErrorKind, Result};
+
+const INCLUDE: &str = ";; @include";
+
+/// Content of the rule file at `path`, with its includes expanded
+pub fn read<L: Lang>(path: impl AsRef<Path>) -> Result<String> {
+    expand::<L>(path.as_ref(), &mut vec![])
+}
+
+fn expand<L: Lang>(path: &Path, including: &mut Vec<PathBuf>) -> Result<String> {
+    let key = path
+        .canonicalize()
+        .map_err(|e| IoErrorKind::open(path, e))?;
+    if including.contains(&key) {
+        return Err(Error::IncludeCycle {
+            file: path.display().to_string(),
+        });
+    }
+    let text = fs::read_to_string(path).map_err(|e| IoErrorKind::read(path, e))?;
+    including.push(key);
+
+    let mut expanded = String::with_capacity(text.len());
+    for line in text.lines() {
+        match line.trim_start().strip_prefix(INCLUDE) {
+            Some(name) if name.starts_with(char::is_whitespace) => {
+                let name = name.trim().trim_matches('"');
+                let fragment = match name.ends_with(".scm") || name.ends_with(".tsg") {
+                    true => {
+                        let dir = path.parent().unwrap_or(Path::new(""));
+                        expand::<L>(&dir.join(name), including)?
+                    }
+                    false => L::fragment(name).ok_or_else(|| Error::UnknownFragment {
+                        file: path.display().to_string(),
+                        name: name.to_string(),
+                    })?,
+                };
+                expanded.push_str(&fragment);
+            }
+            _ => expanded.push_str(line),
+        }
+        expanded.push('\n');
+    }
+
+    including.pop();
+    Ok(expanded)
+}
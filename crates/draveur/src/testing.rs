@@ -0,0 +1,206 @@
+//! Harness checking the graphs rules produce for a source snippet, against an expected graph
+//! or a snapshot file, and any other output against a snapshot file.
+//!
+//! Graphs are compared in their [`normalize`]d form, with node ids replaced by the index of
+//! the node in its graph, so expectations stay readable and don't depend on how ids are
+//! derived. Mismatches panic with a line diff of the pretty-printed json.
+//!
+//! Snapshots are rewritten instead of checked when the `UPDATE_SNAPSHOTS` environment
+//! variable is set, e.g. `UPDATE_SNAPSHOTS=1 cargo test`.
+
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::draveur::Draveur;
+use crate::lang::Lang;
+use crate::types::{Graph, NodeId};
+
+pub const UPDATE_SNAPSHOTS: &str = "UPDATE_SNAPSHOTS";
+
+pub struct RuleTest<L: Lang> {
+    draveur: Draveur<L>,
+    path: PathBuf,
+}
+
+impl<L> RuleTest<L>
+where
+    L: Lang + Sync,
+{
+    /// Tests a single query and stanza pair
+    pub fn new(query: impl Into<String>, stanzas: impl Into<String>) -> Result<Self> {
+        let mut draveur = Draveur::new();
+        draveur.add(query.into(), stanzas.into())?;
+        Ok(Self::with(draveur))
+    }
+
+    /// Tests every rule of `draveur`
+    pub fn with(draveur: Draveur<L>) -> Self {
+        Self {
+            draveur,
+            path: format!("snippet.{}", L::EXT).into(),
+        }
+    }
+
+    /// Path snippets are analyzed as, `snippet.<ext>` by default
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    pub fn run(&self, source: &str) -> Result<Vec<Graph>> {
+        self.draveur.analyze_source(&self.path, source.as_bytes())
+    }
+
+    /// Panics unless the graphs of `source` normalize to `expected`
+    #[track_caller]
+    pub fn assert_graphs(&self, source: &str, expected: serde_json::Value) {
+        let actual = pretty(&normalize(&self.graphs(source)));
+        let expected = pretty(&expected);
+        if actual != expected {
+            panic!(
+                "graphs differ (- expected, + actual):\n{}",
+                diff(&expected, &actual)
+            );
+        }
+    }
+
+    /// Panics unless the graphs of `source` normalize to the content of `snapshot`
+    #[track_caller]
+    pub fn assert_snapshot(&self, source: &str, snapshot: impl AsRef<Path>) {
+        assert_snapshot(&pretty(&normalize(&self.graphs(source))), snapshot);
+    }
+
+    #[track_caller]
+    fn graphs(&self, source: &str) -> Vec<Graph> {
+        match self.run(source) {
+            Ok(graphs) => graphs,
+            Err(e) => panic!("failed to run the rules: {e:?}"),
+        }
+    }
+}
+
+/// Panics unless `actual` is the content of `snapshot`, e.g. a rendered diagram
+#[track_caller]
+pub fn assert_snapshot(actual: &str, snapshot: impl AsRef<Path>) {
+    let snapshot = snapshot.as_ref();
+
+    if env::var_os(UPDATE_SNAPSHOTS).is_some() {
+        if let Some(dir) = snapshot.parent() {
+            fs::create_dir_all(dir).expect("failed to create the snapshot directory");
+        }
+        fs::write(snapshot, actual).expect("failed to write the snapshot");
+        return;
+    }
+
+    let expected = match fs::read_to_string(snapshot) {
+        Ok(expected) => expected,
+        Err(e) => panic!(
+            "failed to read snapshot {}: {e}\nrun with {UPDATE_SNAPSHOTS}=1 to create it",
+            snapshot.display()
+        ),
+    };
+    if actual != expected {
+        panic!(
+            "snapshot {} differs (- snapshot, + actual), run with {UPDATE_SNAPSHOTS}=1 to accept:\n{}",
+            snapshot.display(),
+            diff(&expected, actual)
+        );
+    }
+}
+
+/// Graphs as json with node ids replaced by their index within their graph.
+///
+/// Sinks outside of their graph, e.g. linked by [`resolve_calls`](crate::resolve::resolve_calls),
+/// keep their id.
+pub fn normalize(graphs: &[Graph]) -> serde_json::Value {
+    graphs
+        .iter()
+        .map(|graph| {
+            let index = graph
+                .iter()
+                .enumerate()
+                .map(|(i, node)| (node.id(), i as NodeId))
+                .collect::<HashMap<_, _>>();
+
+            graph
+                .iter()
+                .map(|node| {
+                    let edges = node
+                        .edges()
+                        .iter()
+                        .map(|edge| {
+                            let sink = index.get(&edge.sink()).copied();
+                            let sink = sink.unwrap_or(edge.sink());
+                            json!({"sink": sink, "attrs": edge.attrs()})
+                        })
+                        .collect::<Vec<_>>();
+                    json!({"id": index[&node.id()], "edges": edges, "attrs": node.attrs()})
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn pretty(value: &serde_json::Value) -> String {
+    serde_json::to_string_pretty(value).expect("json values always serialize") + "\n"
+}
+
+// unchanged lines shown around each change
+const CONTEXT: usize = 3;
+
+/// Lines of `expected` missing from `actual` prefixed with `-`, added ones with `+`, and a few
+/// unchanged lines around them
+fn diff(expected: &str, actual: &str) -> String {
+    let (a, b) = (
+        expected.lines().collect::<Vec<_>>(),
+        actual.lines().collect::<Vec<_>>(),
+    );
+
+    // longest common subsequence of the lines after each position
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = match a[i] == b[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push((' ', a[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', a[i]));
+            i += 1;
+        } else {
+            lines.push(('+', b[j]));
+            j += 1;
+        }
+    }
+
+    let changed = |k: usize| lines.get(k).is_some_and(|(op, _)| *op != ' ');
+    let mut out = String::new();
+    let mut skipped = false;
+    for (k, (op, line)) in lines.iter().enumerate() {
+        let near = (k.saturating_sub(CONTEXT)..=k + CONTEXT).any(changed);
+        match near {
+            true => {
+                if skipped {
+                    out += "  ...\n";
+                    skipped = false;
+                }
+                out += &format!("{op} {line}\n");
+            }
+            false => skipped = true,
+        }
+    }
+    out
+}
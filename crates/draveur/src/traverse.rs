@@ -0,0 +1,265 @@
+//! Walks over the nodes and edges of a graph, or of every graph of a crawl once
+//! [`resolve_calls`](crate::resolve::resolve_calls) linked them into a single cross-file graph.
+//!
+//! Traversals follow edges by kind, see [`Edges`]. Results come in a stable order: nodes in
+//! the order of their graphs, edges in the order their source lists them.
+
+use roaring::RoaringTreemap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+use crate::types::{Edge, Graph, Node, NodeId};
+use crate::{Error, Result};
+
+/// Edges a traversal follows
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Edges<'a> {
+    /// Every edge but the `_parent` back-edges, which would make every graph cyclic
+    #[default]
+    Forward,
+    All,
+    /// Edges of one of these kinds, e.g. "call" or "resolves_to"
+    Kinds(&'a [&'a str]),
+}
+
+impl Edges<'_> {
+    pub fn follows(&self, edge: &Edge) -> bool {
+        match self {
+            Edges::Forward => !edge.is_parent(),
+            Edges::All => true,
+            Edges::Kinds(kinds) => edge.kind().is_some_and(|kind| kinds.contains(&kind)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    Breadth,
+    Depth,
+}
+
+pub struct Traversal<'g> {
+    nodes: Vec<&'g Node>,
+    // node id -> position in `nodes`
+    index: HashMap<NodeId, usize>,
+    // node id -> nodes with an edge to it, along with that edge
+    incoming: HashMap<NodeId, Vec<(&'g Node, &'g Edge)>>,
+}
+
+impl<'g> Traversal<'g> {
+    /// Traversal of every node of `graphs`, edges to nodes outside of them are ignored
+    pub fn new(graphs: impl IntoIterator<Item = &'g Graph>) -> Self {
+        let nodes = graphs.into_iter().flat_map(Graph::iter).collect::<Vec<_>>();
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id(), i))
+            .collect::<HashMap<_, _>>();
+
+        let mut incoming = HashMap::<_, Vec<_>>::new();
+        for &node in &nodes {
+            for edge in node.edges() {
+                if index.contains_key(&edge.sink()) {
+                    incoming.entry(edge.sink()).or_default().push((node, edge));
+                }
+            }
+        }
+
+        Self {
+            nodes,
+            index,
+            incoming,
+        }
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&'g Node> {
+        self.index.get(&id).map(|&i| self.nodes[i])
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &'g Node> + '_ {
+        self.nodes.iter().copied()
+    }
+
+    /// Nodes with this `name` attribute, e.g. functions, classes or calls
+    pub fn named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'g Node> + 'a {
+        self.nodes().filter(move |node| node.name() == Some(name))
+    }
+
+    /// Nodes `id` has an edge to
+    pub fn successors<'a>(
+        &'a self,
+        id: NodeId,
+        edges: Edges<'a>,
+    ) -> impl Iterator<Item = &'g Node> + 'a {
+        self.node(id)
+            .into_iter()
+            .flat_map(Node::edges)
+            .filter(move |edge| edges.follows(edge))
+            .filter_map(|edge| self.node(edge.sink()))
+    }
+
+    /// Nodes with an edge to `id`
+    pub fn predecessors<'a>(
+        &'a self,
+        id: NodeId,
+        edges: Edges<'a>,
+    ) -> impl Iterator<Item = &'g Node> + 'a {
+        self.incoming
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter(move |(_, edge)| edges.follows(edge))
+            .map(|&(node, _)| node)
+    }
+
+    /// Nodes reachable from `start`, itself included, closest first
+    pub fn bfs<'a>(&'a self, start: NodeId, edges: Edges<'a>) -> Walk<'a, 'g> {
+        Walk::new(self, start, edges, Order::Breadth)
+    }
+
+    /// Nodes reachable from `start`, itself included, in depth-first preorder
+    pub fn dfs<'a>(&'a self, start: NodeId, edges: Edges<'a>) -> Walk<'a, 'g> {
+        Walk::new(self, start, edges, Order::Depth)
+    }
+
+    /// Ids of the nodes reachable from `start`, itself included
+    pub fn reachable(&self, start: NodeId, edges: Edges) -> RoaringTreemap {
+        self.bfs(start, edges).map(Node::id).collect()
+    }
+
+    pub fn is_reachable(&self, from: NodeId, to: NodeId, edges: Edges) -> bool {
+        self.bfs(from, edges).any(|node| node.id() == to)
+    }
+
+    /// Fewest edges leading from a node named `from` to one named `to`, both ends included.
+    ///
+    /// Names can be shared, e.g. by methods of different classes, the path is the shortest
+    /// between any of them.
+    pub fn shortest_path(&self, from: &str, to: &str, edges: Edges) -> Option<Vec<&'g Node>> {
+        // node id -> the node it was first reached from, `None` for the starts
+        let mut previous = HashMap::new();
+        let mut queue = VecDeque::new();
+        for node in self.named(from) {
+            previous.insert(node.id(), None);
+            queue.push_back(node);
+        }
+
+        while let Some(node) = queue.pop_front() {
+            if node.name() == Some(to) {
+                let mut path = vec![node];
+                let mut id = node.id();
+                while let Some(&Some(prev)) = previous.get(&id) {
+                    path.push(self.node(prev)?);
+                    id = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for next in self.successors(node.id(), edges) {
+                if let Entry::Vacant(entry) = previous.entry(next.id()) {
+                    entry.insert(Some(node.id()));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Every node after all the nodes with an edge to it, fails if the edges form a cycle
+    pub fn topological_sort(&self, edges: Edges) -> Result<Vec<&'g Node>> {
+        let mut degrees = self
+            .nodes()
+            .map(|node| (node.id(), self.predecessors(node.id(), edges).count()))
+            .collect::<HashMap<_, _>>();
+
+        let mut queue = self
+            .nodes()
+            .filter(|node| degrees[&node.id()] == 0)
+            .collect::<VecDeque<_>>();
+        let mut sorted = Vec::with_capacity(self.nodes.len());
+        while let Some(node) = queue.pop_front() {
+            sorted.push(node);
+            for next in self.successors(node.id(), edges) {
+                let degree = degrees.get_mut(&next.id()).expect("successors are indexed");
+                *degree -= 1;
+                if *degree == 0 {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        match self.nodes().find(|node| degrees[&node.id()] > 0) {
+            Some(node) => Err(Error::Cycle(node.id())),
+            None => Ok(sorted),
+        }
+    }
+}
+
+impl Graph {
+    pub fn traversal(&self) -> Traversal<'_> {
+        Traversal::new([self])
+    }
+}
+
+/// Breadth or depth-first walk, see [`Traversal::bfs`] and [`Traversal::dfs`]
+pub struct Walk<'a, 'g> {
+    traversal: &'a Traversal<'g>,
+    edges: Edges<'a>,
+    order: Order,
+    pending: VecDeque<&'g Node>,
+    seen: RoaringTreemap,
+}
+
+impl<'a, 'g> Walk<'a, 'g> {
+    fn new(traversal: &'a Traversal<'g>, start: NodeId, edges: Edges<'a>, order: Order) -> Self {
+        let mut walk = Self {
+            traversal,
+            edges,
+            order,
+            pending: traversal.node(start).into_iter().collect(),
+            seen: RoaringTreemap::new(),
+        };
+        if let Order::Breadth = order {
+            walk.seen.insert(start);
+        }
+        walk
+    }
+}
+
+impl<'g> Iterator for Walk<'_, 'g> {
+    type Item = &'g Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = match self.order {
+            // nodes are marked as they are queued so each is queued once
+            Order::Breadth => {
+                let node = self.pending.pop_front()?;
+                for next in self.traversal.successors(node.id(), self.edges) {
+                    if self.seen.insert(next.id()) {
+                        self.pending.push_back(next);
+                    }
+                }
+                node
+            }
+            // nodes are marked as they are visited, so a node stacked twice is visited from
+            // the latest path reaching it, as a recursive walk would
+            Order::Depth => {
+                let node = loop {
+                    let node = self.pending.pop_back()?;
+                    if self.seen.insert(node.id()) {
+                        break node;
+                    }
+                };
+                let successors = self.traversal.successors(node.id(), self.edges);
+                let unseen = successors
+                    .filter(|next| !self.seen.contains(next.id()))
+                    .collect::<Vec<_>>();
+                // the first edge is walked first
+                self.pending.extend(unseen.into_iter().rev());
+                node
+            }
+        };
+        Some(node)
+    }
+}
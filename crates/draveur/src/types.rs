@@ -1,30 +1,37 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::Debug,
-    sync::atomic::{AtomicUsize, Ordering},
 };
 
-use roaring::RoaringBitmap;
-use serde::{Deserialize, Serialize, Serializer};
+use roaring::RoaringTreemap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tree_sitter_graph::graph as tsg;
 
-use crate::Result;
-pub(crate) type Attributes = HashMap<String, Value>;
-pub(crate) type NodeId = usize;
+use crate::hash::chain;
+use crate::{Error, Result};
 
-static ATOMIC_UID: AtomicUsize = AtomicUsize::new(0);
+// ordered so serialized graphs are the same across runs
+pub type Attributes = BTreeMap<String, Value>;
+pub type NodeId = u64;
 
-#[derive(Deserialize, PartialEq, Clone)]
-#[serde(tag = "type")]
+// ids stay below 2^53 so they survive json consumers using doubles, e.g. the viewer
+const ID_MASK: NodeId = (1 << 53) - 1;
+
+// attributes identifying a node within its graph
+const ID_ATTRS: [&str; 5] = ["type", "start_row", "start_col", "end_row", "end_col"];
+
+// debug attributes tsg sets while executing stanzas, left out of converted graphs
+pub(crate) const LOCATION_ATTR: &str = "__location";
+pub(crate) const VARIABLE_ATTR: &str = "__variable";
+// syntax node matched by the stanza creating a node, tells which subgraph it belongs to
+pub(crate) const MATCH_ATTR: &str = "__match";
+
+#[derive(PartialEq, Clone)]
 pub enum Value {
-    #[serde(rename = "null")]
     Null,
-    #[serde(rename = "bool")]
     Boolean { bool: bool },
-    #[serde(rename = "int")]
     Integer { int: u32 },
-    #[serde(rename = "string")]
     String { string: String },
-    #[serde(rename = "list")]
     List { list: Vec<Value> },
 }
 
@@ -43,6 +50,69 @@ impl Serialize for Value {
     }
 }
 
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // same shapes as `Serialize`, or tagged as earlier versions read and wrote them, e.g.
+        // `{"type": "int", "int": 3}`
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Plain {
+            Null(()),
+            Boolean(bool),
+            Integer(u32),
+            String(String),
+            List(Vec<Value>),
+            Tagged(Tagged),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(tag = "type")]
+        enum Tagged {
+            #[serde(rename = "null")]
+            Null,
+            #[serde(rename = "bool")]
+            Boolean { bool: bool },
+            #[serde(rename = "int")]
+            Integer { int: u32 },
+            #[serde(rename = "string")]
+            String { string: String },
+            #[serde(rename = "list")]
+            List { list: Vec<Value> },
+        }
+
+        Ok(match Plain::deserialize(deserializer)? {
+            Plain::Null(()) | Plain::Tagged(Tagged::Null) => Value::Null,
+            Plain::Boolean(bool) | Plain::Tagged(Tagged::Boolean { bool }) => {
+                Value::Boolean { bool }
+            }
+            Plain::Integer(int) | Plain::Tagged(Tagged::Integer { int }) => Value::Integer { int },
+            Plain::String(string) | Plain::Tagged(Tagged::String { string }) => {
+                Value::String { string }
+            }
+            Plain::List(list) | Plain::Tagged(Tagged::List { list }) => Value::List { list },
+        })
+    }
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String { string } => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<u32> {
+        match self {
+            Value::Integer { int } => Some(*int),
+            _ => None,
+        }
+    }
+}
+
 impl Debug for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -73,6 +143,49 @@ impl From<String> for Value {
     }
 }
 
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String {
+            string: value.to_string(),
+        }
+    }
+}
+
+impl TryFrom<&tsg::Value> for Value {
+    type Error = Error;
+
+    fn try_from(value: &tsg::Value) -> Result<Self> {
+        let list = |values: &mut dyn Iterator<Item = &tsg::Value>| {
+            Ok(Value::List {
+                list: values.map(Value::try_from).collect::<Result<_>>()?,
+            })
+        };
+
+        match value {
+            tsg::Value::Null => Ok(Value::Null),
+            tsg::Value::Boolean(bool) => Ok(Value::Boolean { bool: *bool }),
+            tsg::Value::Integer(int) => Ok(Value::Integer { int: *int }),
+            tsg::Value::String(string) => Ok(Value::String {
+                string: string.clone(),
+            }),
+            tsg::Value::List(values) => list(&mut values.iter()),
+            tsg::Value::Set(values) => list(&mut values.iter()),
+            // references only make sense within the tree they were built from
+            tsg::Value::SyntaxNode(_) | tsg::Value::GraphNode(_) => {
+                Err(Error::other(format!("unsupported attribute value {value}")))
+            }
+        }
+    }
+}
+
+fn attributes(attrs: &tsg::Attributes) -> Result<Attributes> {
+    attrs
+        .iter()
+        .filter(|(name, _)| ![LOCATION_ATTR, VARIABLE_ATTR, MATCH_ATTR].contains(&name.as_str()))
+        .map(|(name, value)| Ok((name.to_string(), Value::try_from(value)?)))
+        .collect()
+}
+
 impl<T: Into<Value>> From<Vec<T>> for Value {
     fn from(value: Vec<T>) -> Self {
         Value::List {
@@ -87,6 +200,37 @@ pub struct Edge {
     attrs: Attributes,
 }
 
+impl Edge {
+    pub fn new(sink: NodeId, kind: &str) -> Self {
+        Self {
+            sink,
+            attrs: Attributes::from([("kind".to_string(), kind.into())]),
+        }
+    }
+
+    pub fn sink(&self) -> NodeId {
+        self.sink
+    }
+
+    pub fn get(&self, k: &str) -> Option<&Value> {
+        self.attrs.get(k)
+    }
+
+    pub fn attrs(&self) -> &Attributes {
+        &self.attrs
+    }
+
+    /// Edge annotation set by the stanzas, e.g. "call", "method" or "_parent"
+    pub fn kind(&self) -> Option<&str> {
+        self.get("kind").and_then(Value::as_str)
+    }
+
+    /// Back-edges pointing from a child to its enclosing definition
+    pub fn is_parent(&self) -> bool {
+        self.kind() == Some("_parent")
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Node {
     id: NodeId,
@@ -106,46 +250,116 @@ impl Node {
     pub fn get(&self, k: &str) -> Option<&Value> {
         self.attrs.get(k)
     }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Adds an edge unless one of the same kind to the same sink already exists
+    pub fn add_edge(&mut self, edge: Edge) {
+        if !self
+            .edges
+            .iter()
+            .any(|e| e.sink == edge.sink && e.kind() == edge.kind())
+        {
+            self.edges.push(edge);
+        }
+    }
+
+    /// Keeps the edges for which `f` returns true
+    pub fn retain_edges(&mut self, f: impl FnMut(&Edge) -> bool) {
+        self.edges.retain(f);
+    }
+
+    pub fn attrs(&self) -> &Attributes {
+        &self.attrs
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get("name").and_then(Value::as_str)
+    }
+
+    /// Tree-sitter node type, e.g. "function_definition" or "call"
+    pub fn node_type(&self) -> Option<&str> {
+        self.get("type").and_then(Value::as_str)
+    }
 }
 
 //NOTE: i'm making the assumption that graphs are already serialized with nodes in order
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Graph(Vec<Node>);
 
 impl Graph {
-    pub fn deser(value: serde_json::Value) -> Result<Self> {
-        let mut graph = serde_json::from_value::<Graph>(value)?;
-        graph.init();
+    /// Subgraph of the stanza output `graph` made of the nodes `keep` selects, by index.
+    ///
+    /// Edges to nodes left out are dropped. `seed` identifies the subgraph, e.g. from its
+    /// file, rule and span, node ids are derived from it so the same code always yields the
+    /// same ids.
+    pub(crate) fn from_tsg(graph: &tsg::Graph, keep: &[bool], seed: u64) -> Result<Self> {
+        let nodes = graph
+            .iter_nodes()
+            .filter(|node| keep[node.index()])
+            .map(|node| {
+                let edges = graph[node]
+                    .iter_edges()
+                    .filter(|(sink, _)| keep[sink.index()])
+                    .map(|(sink, edge)| {
+                        Ok(Edge {
+                            sink: sink.index() as NodeId,
+                            attrs: attributes(&edge.attributes)?,
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+
+                Ok(Node {
+                    id: node.index() as NodeId,
+                    edges,
+                    attrs: attributes(&graph[node].attributes)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let mut graph = Graph(nodes);
+        graph.init(seed)?;
         Ok(graph)
     }
 
-    /// ensure subgraphs each have globally unique node ids
-    fn init(&mut self) {
-        let mut node_iter = self.iter_mut();
-
-        fn _offset_edges(node: &mut Node, offset: NodeId) {
-            for edge in node.edges.iter_mut() {
-                edge.sink += offset;
+    /// ensure subgraphs each have globally unique, stable node ids
+    ///
+    /// Every edge is rewritten through the map of old to new ids, so edges must target nodes
+    /// of the graph and ids must be unique within it.
+    fn init(&mut self, seed: u64) -> Result<()> {
+        // nodes with the same type and span are told apart by their order
+        let mut seen = HashMap::new();
+        let mut ids = HashMap::with_capacity(self.0.len());
+        for node in self.iter() {
+            let key = ID_ATTRS
+                .iter()
+                .filter_map(|k| Some((k, node.get(k)?)))
+                .fold(seed, |h, (k, v)| chain(h, format!("{k}={v:?}").as_bytes()));
+            let n = seen.entry(key).or_insert(0u64);
+            *n += 1;
+            let id = chain(key, &n.to_le_bytes()) & ID_MASK;
+            if ids.insert(node.id, id).is_some() {
+                return Err(Error::DuplicateNode(node.id));
             }
         }
 
-        let root_id = match node_iter.next() {
-            Some(root) => {
-                root.id = ATOMIC_UID.fetch_add(1, Ordering::Relaxed);
-                _offset_edges(root, root.id);
-                root.id
+        for node in self.iter_mut() {
+            for edge in node.edges.iter_mut() {
+                edge.sink = *ids.get(&edge.sink).ok_or(Error::DanglingEdge {
+                    node: node.id,
+                    sink: edge.sink,
+                })?;
             }
-            None => return,
-        };
-
-        for node in node_iter {
-            node.id = ATOMIC_UID.fetch_add(1, Ordering::Relaxed);
-            _offset_edges(node, root_id);
+            node.id = ids[&node.id];
         }
+        Ok(())
     }
 
-    pub fn ids(&self) -> RoaringBitmap {
-        RoaringBitmap::from_iter(self.iter().map(|node| node.id as u32))
+    /// Ids of every node, in a treemap as ids take up to 53 bits
+    pub fn ids(&self) -> RoaringTreemap {
+        RoaringTreemap::from_iter(self.iter().map(|node| node.id))
     }
 
     pub fn iter(&self) -> std::slice::Iter<'_, Node> {
@@ -168,40 +382,3 @@ impl Graph {
         self.iter_mut().filter(|n| n.is_leaf())
     }
 }
-
-// macro_rules! edge {
-//     ($sink:expr => $(($label:literal,$value:expr)),+ ) => {
-//         {
-//             let mut attrs: HashMap<String, Value> = HashMap::new();
-//             $(
-//                 attrs.insert(format!("{}", $label), Value::from($value));
-//             )*
-//             Edge {
-//                 sink: $sink,
-//                 attrs,
-//             }
-//         }
-//     };
-// }
-// pub fn merge<R>(graphs: &mut [Graph], refers_to: R)
-// where
-//     R: Fn(&Node, &Node) -> bool,
-// {
-//     // lookup table
-//     let root_nodes: Vec<Node> = graphs
-//         .iter()
-//         .filter_map(|g| g.root().map(|n| n.clone()))
-//         .collect();
-//
-//     // observe: each graph can be processed independently
-//     for g in graphs.iter_mut() {
-//         for leaf in g.iter_leafs_mut() {
-//             for node in &root_nodes {
-//                 if refers_to(leaf, node) {
-//                     // add some check on if node.id in g.ids()
-//                     leaf.edges.push(edge!(node.id => ("foreign", true)));
-//                 }
-//             }
-//         }
-//     }
-// }
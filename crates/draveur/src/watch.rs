@@ -0,0 +1,335 @@
+//! Keeps the graphs of crawled paths up to date as files change on disk.
+//!
+//! Changed paths go through the same `ignore` filters as the initial crawl, only the files
+//! they affect are re-analyzed and each batch of changes is reported as a [`Delta`].
+
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
+use std::time::{Duration, Instant};
+
+use crate::crawl::Crawler;
+use crate::draveur::Draveur;
+use crate::lang::Lang;
+use crate::types::Graph;
+use crate::{Diagnostic, Error, IoErrorKind, Result};
+
+// quiet period closing a batch of events, editors often write a file several times per save
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+// how often a watch waiting for events checks whether it was cancelled
+const POLL: Duration = Duration::from_millis(100);
+
+// changing these can hide or reveal any file, so they trigger a full crawl
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Changes to the graphs caused by one batch of file events
+#[derive(Debug, Default)]
+pub struct Delta {
+    /// Files analyzed again with their new subgraphs, possibly none
+    pub updated: Vec<(PathBuf, Vec<Graph>)>,
+    /// Files deleted or no longer crawled
+    pub removed: Vec<PathBuf>,
+    /// Files that failed to process when not failing fast, they keep no subgraphs. Their
+    /// failures are kept in [`Watch::diagnostics`] until they process again
+    pub failed: Vec<PathBuf>,
+    /// Time spent analyzing the batch
+    pub elapsed: Duration,
+}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.updated.is_empty() && self.removed.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// Iterator over the [`Delta`]s of the watched paths, blocking until files change.
+///
+/// The first delta is the initial crawl, holding every file. Iteration ends once the
+/// draveur's [`CancellationToken`](crate::cancel::CancellationToken) is cancelled.
+///
+/// Re-analyzed files come without links to other graphs, so those (e.g.
+/// [`resolve_calls`](crate::resolve::resolve_calls)) have to be computed again from
+/// [`Watch::graphs`] after each delta.
+pub struct Watch<'d, L: Lang> {
+    draveur: &'d Draveur<L>,
+    crawlers: Vec<Crawler>,
+    files: BTreeMap<PathBuf, Vec<Graph>>,
+    diagnostics: BTreeMap<PathBuf, Diagnostic>,
+    // paths of the diagnostics that are entries the walker could not read, not files
+    unreadable: BTreeSet<PathBuf>,
+    events: Receiver<notify::Result<Event>>,
+
+    // initial crawl, yielded first
+    initial: Option<Delta>,
+
+    // stops watching once dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl<L> Draveur<L>
+where
+    L: Lang + Sync,
+{
+    /// Crawls `paths` then watches them for changes
+    pub fn watch<P: AsRef<Path>>(&self, paths: &[P]) -> Result<Watch<'_, L>> {
+        let (tx, events) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+
+        let mut roots = vec![];
+        for path in paths {
+            // events carry absolute paths, so crawl from there too
+            let path = path
+                .as_ref()
+                .canonicalize()
+                .map_err(|e| IoErrorKind::open(path, e))?;
+            watcher.watch(&path, RecursiveMode::Recursive)?;
+            roots.push(path);
+        }
+
+        let mut watch = Watch {
+            draveur: self,
+            crawlers: self.crawlers(&roots),
+            files: BTreeMap::new(),
+            diagnostics: BTreeMap::new(),
+            unreadable: BTreeSet::new(),
+            events,
+            initial: None,
+            _watcher: watcher,
+        };
+
+        let start = Instant::now();
+        let delta = watch.rescan()?;
+        watch.initial = Some(Delta {
+            elapsed: start.elapsed(),
+            ..delta
+        });
+        Ok(watch)
+    }
+}
+
+impl<L> Watch<'_, L>
+where
+    L: Lang + Sync,
+{
+    /// Current subgraphs of every crawled file
+    pub fn files(&self) -> &BTreeMap<PathBuf, Vec<Graph>> {
+        &self.files
+    }
+
+    /// Current failure of every crawled file that failed to process, or entry that could not
+    /// be read, by path
+    pub fn diagnostics(&self) -> &BTreeMap<PathBuf, Diagnostic> {
+        &self.diagnostics
+    }
+
+    /// Number of crawled files, including the ones that failed but not the entries that
+    /// could not be read
+    pub fn file_count(&self) -> usize {
+        self.files.len() + self.diagnostics.len() - self.unreadable.len()
+    }
+
+    pub fn graphs(&self) -> impl Iterator<Item = &Graph> {
+        self.files.values().flatten()
+    }
+
+    // every known file, whether it processed or failed
+    fn known(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.keys().chain(self.diagnostics.keys())
+    }
+
+    fn rescan(&mut self) -> Result<Delta> {
+        let mut files = BTreeMap::new();
+        let mut diagnostics = BTreeMap::new();
+        let mut unreadable = BTreeSet::new();
+        for crawler in &self.crawlers {
+            let crawled = self.draveur.crawl(crawler)?;
+            files.extend(crawled.files);
+            unreadable.extend(crawled.unreadable.iter().map(|d| d.path.clone()));
+            diagnostics.extend(
+                crawled
+                    .failed
+                    .into_iter()
+                    .chain(crawled.unreadable)
+                    .map(|d| (d.path.clone(), d)),
+            );
+        }
+
+        let removed = self
+            .known()
+            .filter(|path| !files.contains_key(*path) && !diagnostics.contains_key(*path))
+            .cloned()
+            .collect();
+        self.files = files;
+        self.diagnostics = diagnostics;
+        self.unreadable = unreadable;
+
+        let updated = self
+            .files
+            .iter()
+            .map(|(path, graphs)| (path.clone(), graphs.clone()))
+            .collect();
+        Ok(Delta {
+            updated,
+            removed,
+            failed: self.diagnostics.keys().cloned().collect(),
+            ..Default::default()
+        })
+    }
+
+    fn update(&mut self, changed: BTreeSet<PathBuf>) -> Result<Delta> {
+        let mut delta = Delta::default();
+
+        // files to analyze again, with the index of the crawler they belong to
+        let mut targets = BTreeMap::new();
+        for path in changed {
+            if self.draveur.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let accepted = self
+                .crawlers
+                .iter()
+                .enumerate()
+                .flat_map(|(i, c)| c.accepted(&path).into_iter().map(move |f| (f, i)))
+                .collect::<BTreeMap<_, _>>();
+
+            // known files under a deleted, renamed or newly ignored path
+            delta.removed.extend(
+                self.known()
+                    .filter(|f| f.starts_with(&path) && !accepted.contains_key(*f))
+                    .cloned(),
+            );
+            targets.extend(accepted);
+        }
+
+        for path in &delta.removed {
+            self.files.remove(path);
+            self.diagnostics.remove(path);
+            self.unreadable.remove(path);
+        }
+
+        for (target, root) in targets {
+            if self.draveur.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            // named as in the initial crawl, so the file keeps its node ids
+            let root = &self.crawlers[root];
+            let crawler = self
+                .draveur
+                .crawl_opts(&target)
+                .base(root.base())
+                .prefix(root.prefix())
+                .build();
+            let crawled = self.draveur.crawl(&crawler)?;
+            for (path, graphs) in crawled.files {
+                self.diagnostics.remove(&path);
+                self.unreadable.remove(&path);
+                self.files.insert(path.clone(), graphs.clone());
+                delta.updated.push((path, graphs));
+            }
+            for diagnostic in crawled.failed {
+                self.unreadable.remove(&diagnostic.path);
+                self.fail(&mut delta, diagnostic);
+            }
+            for diagnostic in crawled.unreadable {
+                self.unreadable.insert(diagnostic.path.clone());
+                self.fail(&mut delta, diagnostic);
+            }
+        }
+        Ok(delta)
+    }
+
+    fn fail(&mut self, delta: &mut Delta, diagnostic: Diagnostic) {
+        self.files.remove(&diagnostic.path);
+        delta.failed.push(diagnostic.path.clone());
+        self.diagnostics.insert(diagnostic.path.clone(), diagnostic);
+    }
+
+    /// Waits for the next event, `None` once cancelled or once the watcher is gone
+    fn wait(&self) -> Option<notify::Result<Event>> {
+        while !self.draveur.is_cancelled() {
+            match self.events.recv_timeout(POLL) {
+                Ok(event) => return Some(event),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+        None
+    }
+
+    /// Waits for the next batch of events, `None` once cancelled or once the watcher is gone
+    fn batch(&self) -> Option<Result<(BTreeSet<PathBuf>, bool)>> {
+        let mut changed = BTreeSet::new();
+        let mut rescan = false;
+
+        let mut next = self.wait();
+        while let Some(event) = next {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => return Some(Err(e.into())),
+            };
+
+            let relevant = !matches!(
+                event.kind,
+                EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_))
+            );
+            if relevant {
+                rescan |= event.need_rescan()
+                    || event.paths.iter().any(|p| {
+                        p.file_name()
+                            .is_some_and(|name| IGNORE_FILES.iter().any(|f| name == *f))
+                    });
+                changed.extend(event.paths);
+            }
+
+            next = match self.events.recv_timeout(DEBOUNCE) {
+                Ok(event) => Some(event),
+                Err(RecvTimeoutError::Timeout) if changed.is_empty() && !rescan => self.wait(),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return None,
+            };
+        }
+
+        match changed.is_empty() && !rescan {
+            true => None,
+            false => Some(Ok((changed, rescan))),
+        }
+    }
+}
+
+impl<L> Iterator for Watch<'_, L>
+where
+    L: Lang + Sync,
+{
+    type Item = Result<Delta>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(delta) = self.initial.take() {
+            return Some(Ok(delta));
+        }
+
+        loop {
+            let batch = self.batch()?;
+            let start = Instant::now();
+            let delta = match batch {
+                Ok((_, true)) => self.rescan(),
+                Ok((changed, false)) => self.update(changed),
+                Err(e) => Err(e),
+            }
+            .map(|delta| Delta {
+                elapsed: start.elapsed(),
+                ..delta
+            });
+
+            if let Err(Error::Cancelled) = delta {
+                return None;
+            }
+            // e.g. only files the crawl skips changed
+            if !delta.as_ref().is_ok_and(Delta::is_empty) {
+                return Some(delta);
+            }
+        }
+    }
+}
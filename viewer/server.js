@@ -4,6 +4,8 @@ const chokidar = require('chokidar');
 const { spawn } = require('child_process');
 const path = require('path');
 const http = require('http');
+const os = require('os');
+const crypto = require('crypto');
 
 const watchDir = process.argv[2];
 if (!watchDir) {
@@ -13,6 +15,9 @@ if (!watchDir) {
 
 const resolvedWatchDir = path.resolve(watchDir);
 const draveurBin = path.resolve(__dirname, '../target/release/draveur-python');
+// outside the watched tree, so cache writes don't trigger the watcher, one per watched dir
+const cacheKey = crypto.createHash('sha256').update(resolvedWatchDir).digest('hex').slice(0, 16);
+const cacheDir = path.join(os.tmpdir(), 'draveur-cache', cacheKey);
 
 let latestGraph = null, lastElapsed = null;
 
@@ -24,24 +29,22 @@ app.get('/', (_, res) => res.sendFile(path.join(__dirname, 'index.html')));
 
 function runDraveur() {
   return new Promise((resolve, reject) => {
-    const proc = spawn(draveurBin, [], { cwd: resolvedWatchDir });
+    const args = ['analyze', '--format', 'json', '--keep-going', '--cache', cacheDir];
+    const proc = spawn(draveurBin, args, { cwd: resolvedWatchDir });
     let stdout = '', stderr = '';
     proc.stdout.on('data', d => stdout += d);
     proc.stderr.on('data', d => stderr += d);
     proc.on('close', code => {
       if (code !== 0) return reject(new Error(`draveur exited with code ${code}: ${stderr}`));
+      let doc;
       try {
-        const graphs = [];
-        let buffer = '', depth = 0;
-        for (const line of stdout.trim().split('\n')) {
-          buffer += line;
-          depth += (line.match(/\[/g) || []).length - (line.match(/\]/g) || []).length;
-          if (depth === 0 && buffer.trim()) { graphs.push(JSON.parse(buffer)); buffer = ''; }
-        }
-        resolve(graphs.flat());
+        doc = JSON.parse(stdout);
       } catch (e) {
-        reject(new Error(`Failed to parse JSON: ${e.message}`));
+        return reject(new Error(`Failed to parse JSON: ${e.message}`));
       }
+      if (doc.version !== 1) return reject(new Error(`Unsupported document version ${doc.version}`));
+      doc.diagnostics.forEach(d => console.warn(`warning: ${d.message}`));
+      resolve(doc.graphs.flat());
     });
     proc.on('error', reject);
   });